@@ -0,0 +1,208 @@
+//! Reads the history of the garden from the local git repository, which the
+//! production image ships along with git. Nothing here touches the network,
+//! and when there is no repository or no git binary it simply finds nothing.
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::process::Command;
+
+use crate::utils::Revision;
+
+// Separators git puts around each commit and between its fields, they never
+// show up in commit messages or diffs of text files.
+const RECORD_SEPARATOR: char = '\u{1e}';
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+/// Every post that was renamed or moved, from each of its old paths to the
+/// path it was last renamed to. Paths are relative to `documents_path`.
+pub fn detect_renames(documents_path: &str) -> HashMap<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(documents_path)
+        .args([
+            "log",
+            "-M",
+            "--diff-filter=R",
+            "--name-status",
+            "--format=",
+            "--relative",
+            "--",
+            ".",
+        ])
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => {
+            resolve_rename_chains(&parse_renames(&String::from_utf8_lossy(&output.stdout)))
+        }
+        Ok(output) => {
+            eprintln!(
+                "Could not read renames from git: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            HashMap::new()
+        }
+        Err(e) => {
+            eprintln!("Could not run git to read renames: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Every commit that touched the post, newest first. Follows the post across
+/// renames. The diffs are left out, they are only read for the history page.
+pub fn post_history(documents_path: &str, file_path: &str) -> Vec<Revision> {
+    post_log(documents_path, file_path, false)
+        .into_iter()
+        .map(|(revision, _)| revision)
+        .collect()
+}
+
+/// The diff each commit in [`post_history`] introduced, by commit hash.
+pub fn post_diffs(documents_path: &str, file_path: &str) -> HashMap<String, String> {
+    post_log(documents_path, file_path, true)
+        .into_iter()
+        .map(|(revision, diff)| (revision.hash, diff))
+        .collect()
+}
+
+fn post_log(documents_path: &str, file_path: &str, with_patch: bool) -> Vec<(Revision, String)> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(documents_path)
+        .args(["log", "--follow", "--no-color"])
+        .arg(if with_patch { "--patch" } else { "--no-patch" })
+        .args(["--format=%x1e%H%x1f%aI%x1f%s%x1f", "--", file_path])
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_history(&String::from_utf8_lossy(&output.stdout))
+        }
+        // Not being in a repository already showed up when looking for renames
+        _ => Vec::new(),
+    }
+}
+
+/// Parses the output of `git log` with the format used by [`post_history`],
+/// along with the patch of each commit when it has one.
+pub fn parse_history(log_output: &str) -> Vec<(Revision, String)> {
+    log_output
+        .split(RECORD_SEPARATOR)
+        .filter_map(|record| {
+            let mut fields = record.splitn(4, FIELD_SEPARATOR);
+            let hash = fields.next()?.trim();
+            let date = DateTime::parse_from_rfc3339(fields.next()?.trim()).ok()?;
+            let message = fields.next()?.trim();
+            let diff = fields.next().unwrap_or_default().trim();
+
+            Some((
+                Revision {
+                    hash: hash.to_string(),
+                    date: date.with_timezone(&Utc),
+                    message: message.to_string(),
+                },
+                diff.to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Parses `git log --name-status` output into `(old, new)` pairs of renamed
+/// markdown files, newest rename first like git lists them.
+pub fn parse_renames(log_output: &str) -> Vec<(String, String)> {
+    log_output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let status = fields.next()?;
+            if !status.starts_with('R') {
+                return None;
+            }
+            let old = fields.next()?;
+            let new = fields.next()?;
+
+            (old.ends_with(".md") && new.ends_with(".md"))
+                .then(|| (old.to_string(), new.to_string()))
+        })
+        .collect()
+}
+
+/// Follows renames of renames, so every old path leads straight to the
+/// latest one instead of to another old path.
+pub fn resolve_rename_chains(renames: &[(String, String)]) -> HashMap<String, String> {
+    let mut latest_paths: HashMap<String, String> = HashMap::new();
+    for (old, new) in renames.iter().rev() {
+        for latest_path in latest_paths.values_mut() {
+            if latest_path == old {
+                *latest_path = new.clone();
+            }
+        }
+        latest_paths.insert(old.clone(), new.clone());
+    }
+    // A post moved back to where it used to be is not a rename anymore
+    latest_paths.retain(|old, new| old != new);
+
+    latest_paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_renames_only_keeps_markdown_renames() {
+        let output = "R100\tjournal/old.md\tjournal/2025/old.md\n\
+            R087\tcover.png\timages/cover.png\n\
+            M\twelcome.md\n\
+            \n\
+            R095\thello.md\tjournal/hello.md\n";
+
+        assert_eq!(
+            parse_renames(output),
+            vec![
+                (
+                    "journal/old.md".to_string(),
+                    "journal/2025/old.md".to_string()
+                ),
+                ("hello.md".to_string(), "journal/hello.md".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_history() {
+        let output = "\u{1e}abc123\u{1f}2025-10-01T10:00:00+02:00\u{1f}Fix typos\u{1f}\n\n\
+            diff --git a/welcome.md b/welcome.md\n\
+            @@ -1 +1 @@\n\
+            -Helo\n\
+            +Hello\n\
+            \u{1e}def456\u{1f}2025-09-15T09:30:00+00:00\u{1f}Add welcome post\u{1f}\n";
+
+        let revisions = parse_history(output);
+        assert_eq!(revisions.len(), 2);
+        let (revision, diff) = &revisions[0];
+        assert_eq!(revision.hash, "abc123");
+        assert_eq!(revision.message, "Fix typos");
+        assert_eq!(revision.date.to_rfc3339(), "2025-10-01T08:00:00+00:00");
+        assert!(diff.starts_with("diff --git"));
+        assert!(diff.ends_with("+Hello"));
+        assert_eq!(revisions[1].0.message, "Add welcome post");
+        assert!(revisions[1].1.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_rename_chains() {
+        // Newest first, as git log prints them
+        let renames = [
+            ("b.md".to_string(), "c.md".to_string()),
+            ("a.md".to_string(), "b.md".to_string()),
+            ("x.md".to_string(), "y.md".to_string()),
+        ];
+
+        let latest_paths = resolve_rename_chains(&renames);
+        assert_eq!(latest_paths.get("a.md").map(String::as_str), Some("c.md"));
+        assert_eq!(latest_paths.get("b.md").map(String::as_str), Some("c.md"));
+        assert_eq!(latest_paths.get("x.md").map(String::as_str), Some("y.md"));
+    }
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Language a post is written in. Each one gets its own search analyzer, so
+/// words are stemmed and stopwords dropped the way that language needs.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "es")]
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+    /// Reads the language from a code like `es` or `en-GB`, the region is
+    /// ignored.
+    pub fn from_code(code: &str) -> Option<Language> {
+        let code = code.trim().to_lowercase();
+        let base = code.split(['-', '_']).next().unwrap_or(&code);
+        Language::ALL
+            .into_iter()
+            .find(|language| language.code() == base)
+    }
+
+    /// ISO 639-1 code, as written in the frontmatter and in `<html lang>`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+        }
+    }
+
+    /// Name of the language in that language, for the language switcher.
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Español",
+        }
+    }
+
+    pub(crate) fn analyzer(&self) -> String {
+        format!("{}_analyzer", self.code())
+    }
+
+    pub(crate) fn snowball_name(&self) -> &'static str {
+        match self {
+            Language::English => "english",
+            Language::Spanish => "spanish",
+        }
+    }
+
+    /// Fields the text of the posts in this language is indexed under.
+    pub(crate) fn title_field(&self) -> String {
+        format!("search_{}_title", self.code())
+    }
+
+    pub(crate) fn content_field(&self) -> String {
+        format!("search_{}_content", self.code())
+    }
+
+    fn stopwords(&self) -> &'static [&'static str] {
+        match self {
+            Language::English => ENGLISH_STOPWORDS,
+            Language::Spanish => SPANISH_STOPWORDS,
+        }
+    }
+
+    /// The text without the words of this language too common to tell posts
+    /// apart. SurrealDB analyzers have no stopword filter, so this is done
+    /// both on what is indexed and on what is searched.
+    pub fn remove_stopwords(&self, text: &str) -> String {
+        let stopwords = self.stopwords();
+        text.split_whitespace()
+            .filter(|word| {
+                let word = word
+                    .trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase();
+                !stopwords.contains(&word.as_str())
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+const ENGLISH_STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "been",
+    "but", "by", "can", "could", "did", "do", "does", "for", "from", "had", "has", "have", "he",
+    "her", "his", "how", "i", "if", "in", "into", "is", "it", "its", "just", "me", "my", "no",
+    "not", "of", "on", "or", "our", "out", "she", "so", "some", "than", "that", "the", "their",
+    "them", "then", "there", "these", "they", "this", "to", "up", "was", "we", "were", "what",
+    "when", "which", "who", "will", "with", "would", "you", "your",
+];
+
+const SPANISH_STOPWORDS: &[&str] = &[
+    "a", "al", "algo", "como", "con", "cuando", "de", "del", "desde", "donde", "el", "ella", "en",
+    "entre", "era", "es", "esa", "ese", "eso", "esta", "este", "esto", "fue", "ha", "hay", "la",
+    "las", "le", "les", "lo", "los", "me", "mi", "muy", "más", "nos", "o", "para", "pero", "por",
+    "que", "se", "si", "sin", "sobre", "son", "su", "sus", "también", "te", "tu", "un", "una",
+    "uno", "y", "ya", "yo",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_code_ignores_region_and_case() {
+        assert_eq!(Language::from_code("es"), Some(Language::Spanish));
+        assert_eq!(Language::from_code("en-GB"), Some(Language::English));
+        assert_eq!(Language::from_code(" ES_es "), Some(Language::Spanish));
+        assert_eq!(Language::from_code("fr"), None);
+    }
+
+    #[test]
+    fn test_remove_stopwords_per_language() {
+        assert_eq!(
+            Language::English.remove_stopwords("The history of the Silent Hill series"),
+            "history Silent Hill series"
+        );
+        assert_eq!(
+            Language::Spanish.remove_stopwords("La historia de la saga, y el final"),
+            "historia saga, final"
+        );
+    }
+}
@@ -1,116 +1,279 @@
+use chrono::{DateTime, Utc};
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::sync::{Arc, RwLock};
+use surrealdb::Surreal;
 use surrealdb::engine::any::Any;
 use surrealdb::engine::any::connect;
-use surrealdb::{Response, Surreal};
-use walkdir::WalkDir;
+use surrealdb::opt::auth::Root;
 
-use crate::types::EdgeFilePath;
+use crate::language::Language;
 use crate::types::Params;
+use crate::types::SuggestedPost;
+use crate::types::{Collections, DatabaseConfig, EdgeFilePath, GraphParams};
 use crate::types::{DEFAULT_SEARCH_LIMIT, GraphData, GraphEdge, GraphNode};
-use crate::utils::{Post, extract_full_metadata};
+use crate::types::{NewsFacets, NewsPage, NewsParams, Redirect, RedirectSource, RenderedPost};
+use crate::utils::{
+    MdMetadata, NOTE_EMBED_PATTERN, Post, PostPath, content_hash, extract_full_metadata,
+    parse_alias, render_hash, resolve_note_reference, settings_hash, suggestion_score,
+};
 
+pub mod git;
+pub mod language;
+pub mod stats;
 pub mod types;
 pub mod utils;
 
+// Bump it whenever what is stored for a post changes, stored snapshots of an
+// older version are then rebuilt from scratch instead of updated
+const SNAPSHOT_VERSION: u32 = 7;
+
+// Below this, suggestions for a missing post are more noise than help
+const MIN_SUGGESTION_SCORE: f64 = 0.4;
+// Longer routes are no typo of a post, and scoring them is quadratic
+const MAX_SUGGESTION_ROUTE_LEN: usize = 200;
+
 pub struct PostsSearchEngine {
     db: Surreal<Any>,
+    indexed_at: DateTime<Utc>,
+    // Rendered posts by render hash, which covers the notes they embed, so a
+    // changed post or note is rendered again under a new key
+    rendered_posts: RwLock<HashMap<String, Arc<RenderedPost>>>,
+    collections: Collections,
+    // Old paths of moved posts, from their `aliases` and git renames, to
+    // where they are now
+    redirects: HashMap<PostPath, Redirect>,
+    // Published posts, for the suggestions of the 404 page
+    suggestion_candidates: Vec<SuggestedPost>,
 }
 
 impl PostsSearchEngine {
-    pub async fn new(repo_path: &str, documents_path: &str) -> PostsSearchEngine {
-        let mut posts: Vec<Post> = Vec::new();
-        for entry in WalkDir::new(documents_path) {
-            let entry = entry.expect("Error while accessing the WalkDir entry");
-            let path = entry.path();
-            if path.is_file()
-                && let Ok(relative_path) = path.strip_prefix(documents_path)
-            {
-                let file_path = relative_path.to_string_lossy().to_string();
-                let file_name = relative_path
-                    .file_name()
-                    .unwrap()
-                    .to_string_lossy()
-                    .to_string();
-                let full_path = format!("{}/{}", documents_path, &file_path);
-
-                match fs::read_to_string(path) {
-                    Ok(content) => {
-                        let post = Post {
-                            file_name,
-                            file_path,
-                            metadata: extract_full_metadata(repo_path, full_path.as_str()).unwrap(),
-                            content,
-                        };
-                        posts.push(post);
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to read file {}: {}", path.display(), e);
+    /// Indexes the posts of every collection. Stores that outlive the process
+    /// keep a snapshot of the last index, and only the posts whose content
+    /// changed since then are parsed and indexed again.
+    pub async fn new(
+        repo_path: &str,
+        collections: &Collections,
+        db_config: &DatabaseConfig,
+    ) -> Result<PostsSearchEngine, surrealdb::Error> {
+        let db = connect(db_config.endpoint.as_str()).await?;
+        if let Some((username, password)) = &db_config.credentials {
+            db.signin(Root { username, password }).await?;
+        }
+        db.use_ns("guslee").use_db("guslee").await?;
+
+        let snapshot: Option<IndexSnapshot> = db
+            .query("SELECT version, settings_hash FROM snapshot:index")
+            .await?
+            .take(0)?;
+        let settings_hash = settings_hash(repo_path, collections);
+        let full_rebuild = db_config.force_rebuild
+            || !snapshot.is_some_and(|snapshot| {
+                snapshot.version == SNAPSHOT_VERSION
+                    && snapshot.settings_hash.as_ref() == Some(&settings_hash)
+            });
+
+        let indexed_hashes: HashMap<String, String> = if full_rebuild {
+            define_schema(&db).await?;
+            HashMap::new()
+        } else {
+            db.query("SELECT file_path, content_hash FROM posts")
+                .await?
+                .take::<Vec<IndexedHash>>(0)?
+                .into_iter()
+                .filter_map(|indexed| Some((indexed.file_path, indexed.content_hash?)))
+                .collect()
+        };
+
+        let mut changed_posts: Vec<Post> = Vec::new();
+        let mut found_paths: HashSet<String> = HashSet::new();
+        // Only markdown files are posts, anything else next to them is an attachment
+        for file in collections.files().into_iter().filter(|file| {
+            file.disk_path
+                .extension()
+                .is_some_and(|extension| extension == "md")
+        }) {
+            let full_path = file.disk_path.to_string_lossy().to_string();
+            match fs::read_to_string(&file.disk_path) {
+                Ok(content) => {
+                    let hash = content_hash(&file.file_path, &content);
+                    if indexed_hashes.get(&file.file_path) == Some(&hash) {
+                        found_paths.insert(file.file_path);
+                        continue;
                     }
+
+                    // A broken post is left out of the index (and removed
+                    // from it) instead of taking the whole garden down
+                    let metadata = match extract_full_metadata(
+                        repo_path,
+                        full_path.as_str(),
+                        &content,
+                        file.collection,
+                    ) {
+                        Ok(metadata) => metadata,
+                        Err(e) => {
+                            log::warn!("Skipped {}, its metadata is invalid: {}", full_path, e);
+                            continue;
+                        }
+                    };
+                    found_paths.insert(file.file_path.clone());
+                    let root = file.collection.root.to_string_lossy();
+                    let metadata = MdMetadata {
+                        revisions: git::post_history(&root, &file.relative_path),
+                        ..metadata
+                    };
+                    let post = Post {
+                        file_name: file
+                            .disk_path
+                            .file_name()
+                            .unwrap()
+                            .to_string_lossy()
+                            .to_string(),
+                        file_path: file.file_path,
+                        collection: file.collection.name.clone(),
+                        metadata,
+                        content,
+                    };
+                    changed_posts.push(post);
+                }
+                Err(e) => {
+                    eprintln!("Failed to read file {}: {}", full_path, e);
                 }
             }
         }
 
-        #[cfg(debug_assertions)]
-        let db = connect("ws://127.0.0.1:8000").await.unwrap();
-
-        #[cfg(not(debug_assertions))]
-        let db = connect("mem://").await.unwrap();
-
-        db.use_ns("guslee").use_db("guslee").await.unwrap();
-        let _  = db.query(
-            "DEFINE TABLE posts SCHEMAFULL;
-            DEFINE FIELD file_name ON posts TYPE string;
-            DEFINE FIELD file_path ON posts TYPE string;
-            DEFINE FIELD metadata ON posts TYPE object;
-            DEFINE FIELD metadata.title ON posts TYPE string;
-            DEFINE FIELD metadata.description ON posts TYPE string;
-            DEFINE FIELD metadata.tags ON posts TYPE array<string>;
-            DEFINE FIELD metadata.date ON posts TYPE string;
-            DEFINE FIELD metadata.topic ON posts TYPE option<string>;
-            DEFINE FIELD metadata.post_source_url ON posts TYPE string;
-            DEFINE FIELD metadata.is_draft ON posts TYPE bool DEFAULT false;
-            DEFINE FIELD metadata.reading_time ON posts TYPE integer;
-            DEFINE FIELD content ON posts TYPE string;
-            DEFINE INDEX file_path_index ON TABLE posts COLUMNS file_path UNIQUE;
-
-            -- Define a custom analyzer
-            DEFINE ANALYZER full_text_analyzer TOKENIZERS class FILTERS lowercase, ascii, edgengram(2, 15);
-            
-            -- Create a full-text search index
-            DEFINE INDEX ml_title ON TABLE posts FIELDS metadata.title SEARCH ANALYZER full_text_analyzer BM25 HIGHLIGHTS;
-            DEFINE INDEX ml_content ON TABLE posts FIELDS content SEARCH ANALYZER full_text_analyzer BM25 HIGHLIGHTS;",
-        )
-        .await;
-        let inserted_posts = db
-            .insert::<Vec<Post>>("posts")
-            .content(posts)
-            .await
-            .unwrap();
+        let removed_paths: Vec<&String> = indexed_hashes
+            .keys()
+            .filter(|file_path| !found_paths.contains(*file_path))
+            .collect();
+        let index_changed = full_rebuild || !changed_posts.is_empty() || !removed_paths.is_empty();
 
-        for inserted_post in inserted_posts.iter() {
-            let mentioned_posts = get_mentioned_posts_in_post_content(inserted_post);
-            if mentioned_posts.is_empty() {
-                continue;
+        for file_path in removed_paths
+            .into_iter()
+            .chain(changed_posts.iter().map(|post| &post.file_path))
+        {
+            db.query("DELETE posts WHERE file_path = $path")
+                .bind(("path", file_path.clone()))
+                .await?
+                .check()?;
+        }
+        if !changed_posts.is_empty() {
+            let records: Vec<IndexedPost> =
+                changed_posts.into_iter().map(IndexedPost::new).collect();
+            db.insert::<Vec<Post>>("posts").content(records).await?;
+        }
+
+        let posts: Vec<Post> = db.query("SELECT * FROM posts").await?.take(0)?;
+        let renames: HashMap<String, String> = collections
+            .iter()
+            .flat_map(|collection| {
+                git::detect_renames(&collection.root.to_string_lossy())
+                    .into_iter()
+                    .map(|(old, new)| (collection.file_path(&old), collection.file_path(&new)))
+            })
+            .collect();
+        let redirects = build_redirects(&posts, &renames);
+        let suggestion_candidates = posts
+            .iter()
+            .filter(|post| !post.metadata.is_draft)
+            .filter_map(SuggestedPost::new)
+            .collect();
+        for post in posts.iter() {
+            for translation in post.metadata.translations.iter() {
+                if !posts.iter().any(|other| other.file_path == *translation) {
+                    eprintln!(
+                        "{} lists {} as a translation, but there is no such post",
+                        post.file_path, translation
+                    );
+                }
             }
+        }
 
-            let source_path = inserted_post.file_path.clone();
+        // Links of unchanged posts may now point to a post that was added,
+        // removed or moved, so edges are rebuilt whenever anything changed
+        if index_changed {
+            db.query("DELETE points_to").await?.check()?;
 
-            for mentioned_path in mentioned_posts {
-                let query_string = "RELATE (SELECT id FROM posts WHERE file_path = $source)->points_to->(SELECT id FROM posts WHERE file_path = $target)";
-                let _ = db
-                    .query(query_string)
-                    .bind(("source", source_path.clone()))
-                    .bind(("target", mentioned_path))
-                    .await
-                    .unwrap();
+            let file_paths: Vec<&str> = posts.iter().map(|post| post.file_path.as_str()).collect();
+            for post in posts.iter() {
+                let mentioned_posts =
+                    get_mentioned_posts_in_post_content(post, &file_paths, &redirects);
+                if mentioned_posts.is_empty() {
+                    continue;
+                }
+
+                let source_path = post.file_path.clone();
+
+                for mentioned_path in mentioned_posts {
+                    let query_string = "RELATE (SELECT id FROM posts WHERE file_path = $source)->points_to->(SELECT id FROM posts WHERE file_path = $target)";
+                    db.query(query_string)
+                        .bind(("source", source_path.clone()))
+                        .bind(("target", mentioned_path))
+                        .await?
+                        .check()?;
+                }
             }
+
+            db.query("UPSERT snapshot:index SET version = $version, settings_hash = $settings_hash, built_at = time::now()")
+                .bind(("version", SNAPSHOT_VERSION))
+                .bind(("settings_hash", settings_hash))
+                .await?
+                .check()?;
         }
 
-        PostsSearchEngine { db }
+        // The snapshot is only written when something changed, so this is when
+        // the stored posts were last touched, not when the server started
+        let indexed_at: Option<DateTime<Utc>> = db
+            .query("SELECT VALUE built_at FROM snapshot:index")
+            .await?
+            .take(0)?;
+
+        Ok(PostsSearchEngine {
+            db,
+            indexed_at: indexed_at.unwrap_or_else(Utc::now),
+            rendered_posts: RwLock::new(HashMap::new()),
+            collections: collections.clone(),
+            redirects,
+            suggestion_candidates,
+        })
     }
 
+    /// When the posts were last indexed, nothing served from the index can
+    /// be newer than this.
+    pub fn indexed_at(&self) -> DateTime<Utc> {
+        self.indexed_at
+    }
+
+    /// Returns the rendered post from the cache, calling `render` only the
+    /// first time this version of the post and its `embedded` notes is
+    /// requested.
+    pub fn rendered_post<F>(
+        &self,
+        post: &Post,
+        embedded: &HashMap<String, Post>,
+        render: F,
+    ) -> Arc<RenderedPost>
+    where
+        F: FnOnce(&Post) -> RenderedPost,
+    {
+        let hash = render_hash(post, embedded);
+        if let Some(rendered) = self.rendered_posts.read().unwrap().get(&hash) {
+            return Arc::clone(rendered);
+        }
+
+        let rendered = Arc::new(render(post));
+        self.rendered_posts
+            .write()
+            .unwrap()
+            .insert(hash, Arc::clone(&rendered));
+
+        rendered
+    }
+
+    /// Searches the posts of every language, or only of `params.lang`, each
+    /// with the analyzer of its language.
     pub async fn query_posts(&self, params: &Params) -> Vec<Post> {
         let limit = match &params.limit {
             Some(l) => l.value(),
@@ -118,39 +281,299 @@ impl PostsSearchEngine {
         };
 
         let query = params.query.as_ref().unwrap().to_lowercase();
-        let mut response: Response = self
+        let languages: Vec<Language> = match params.lang {
+            Some(lang) => vec![lang],
+            None => Language::ALL.to_vec(),
+        };
+
+        let mut matches = Vec::new();
+        let mut scores = Vec::new();
+        for (i, language) in languages.iter().enumerate() {
+            let (title_ref, content_ref) = (i * 2, i * 2 + 1);
+            matches.push(format!(
+                "{} @{}@ $query_{code} OR {} @{}@ $query_{code}",
+                language.title_field(),
+                title_ref,
+                language.content_field(),
+                content_ref,
+                code = language.code()
+            ));
+            scores.push(format!(
+                "(search::score({}) ?? 0) * 2 + (search::score({}) ?? 0)",
+                title_ref, content_ref
+            ));
+        }
+
+        let mut request = self
             .db
             .query(format!(
                 "SELECT *,
-                    search::score(0) AS title_score,
-                    search::score(1) AS content_score,
-                    search::score(0) * 2 + search::score(1) AS combined_score
+                    {} AS combined_score
                 FROM posts 
-                WHERE metadata.title @0@ '{}' 
-                   OR content @1@ '{}'
+                WHERE ({})
                    AND metadata.is_draft = false
+                   AND ($collection = NONE OR collection = $collection)
                 ORDER BY combined_score DESC
                 LIMIT {}",
-                query, query, limit
+                scores.join(" + "),
+                matches.join(" OR "),
+                limit
             ))
-            .await
-            .unwrap();
+            .bind(("collection", params.collection.clone()));
+        for language in languages {
+            // A query made only of stopwords is still searched as it is
+            let language_query = match language.remove_stopwords(&query) {
+                stripped if stripped.is_empty() => query.clone(),
+                stripped => stripped,
+            };
+            request = request.bind((format!("query_{}", language.code()), language_query));
+        }
 
-        response.take(0).unwrap()
+        request.await.unwrap().take(0).unwrap()
     }
 
-    pub async fn get_post(&self, file_path: &str) -> Option<Post> {
+    pub async fn get_post(&self, path: &PostPath) -> Option<Post> {
         self.db
             .query("SELECT * FROM posts WHERE file_path = $path AND metadata.is_draft = false")
-            .bind(("path", file_path.to_string()))
+            .bind(("path", path.as_str().to_string()))
             .await
             .unwrap()
             .take::<Option<Post>>(0)
             .unwrap()
     }
 
-    pub async fn get_overall_graph_data(&self) -> GraphData {
-        let mut posts_result = self.db.query("SELECT * FROM posts").await.unwrap();
+    /// Whether an attachment (`journal/2025/cover.png`) belongs to drafts only,
+    /// its owners being the posts in the same directory. Attachments no post
+    /// sits next to are left public.
+    pub async fn is_draft_attachment(&self, file_path: &str) -> bool {
+        #[derive(Deserialize)]
+        struct Owner {
+            file_path: String,
+            is_draft: bool,
+        }
+
+        let directory = file_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let owners: Vec<Owner> = self
+            .db
+            .query("SELECT file_path, metadata.is_draft AS is_draft FROM posts")
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap_or_default();
+        let mut owners = owners
+            .iter()
+            .filter(|owner| {
+                owner.file_path.rsplit_once('/').map_or("", |(dir, _)| dir) == directory
+            })
+            .peekable();
+
+        owners.peek().is_some() && owners.all(|owner| owner.is_draft)
+    }
+
+    /// The diff each revision of the post introduced, by commit hash. Read
+    /// from git on every call, only the history page needs them.
+    pub fn revision_diffs(&self, post: &Post) -> HashMap<String, String> {
+        match self.collections.resolve(&post.file_path) {
+            Some((collection, relative_path)) => {
+                git::post_diffs(&collection.root.to_string_lossy(), relative_path)
+            }
+            None => HashMap::new(),
+        }
+    }
+
+    /// The public posts `post` transcludes with `![[note]]`, and the ones those
+    /// transclude in turn, by the reference they are embedded with. Drafts are
+    /// never found, so they can't leak into a public page.
+    pub async fn get_embedded_posts(&self, post: &Post) -> HashMap<String, Post> {
+        let embed_pattern = Regex::new(NOTE_EMBED_PATTERN).unwrap();
+        let file_paths: Vec<String> = self
+            .db
+            .query("SELECT VALUE file_path FROM posts WHERE metadata.is_draft = false")
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap_or_default();
+
+        let mut embedded: HashMap<String, Post> = HashMap::new();
+        let mut missing: HashSet<String> = HashSet::new();
+        let mut pending: Vec<String> = vec![post.content.clone()];
+        while let Some(content) = pending.pop() {
+            for cap in embed_pattern.captures_iter(&content) {
+                let reference = cap[1].trim().to_string();
+                if embedded.contains_key(&reference) || missing.contains(&reference) {
+                    continue;
+                }
+
+                let found =
+                    match resolve_note_reference(&reference, file_paths.iter().map(String::as_str))
+                        .and_then(|file_path| PostPath::from_file_path(&file_path).ok())
+                    {
+                        Some(path) => self.get_post(&path).await,
+                        None => None,
+                    };
+                match found {
+                    Some(found) => {
+                        pending.push(found.content.clone());
+                        embedded.insert(reference, found);
+                    }
+                    None => {
+                        missing.insert(reference);
+                    }
+                }
+            }
+        }
+
+        embedded
+    }
+
+    /// The same post in other languages, whether it lists them in its
+    /// `translations` or they list it in theirs.
+    pub async fn get_translations(&self, post: &Post) -> Vec<Post> {
+        self.db
+            .query(
+                "SELECT * FROM posts
+                WHERE file_path != $path
+                   AND (file_path IN $translations OR $path IN metadata.translations)
+                   AND metadata.is_draft = false
+                ORDER BY metadata.lang",
+            )
+            .bind(("path", post.file_path.clone()))
+            .bind(("translations", post.metadata.translations.clone()))
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap_or_default()
+    }
+
+    pub fn collections(&self) -> &Collections {
+        &self.collections
+    }
+
+    /// Where a post that used to live at `path` is now, if it was moved.
+    pub fn redirect_for(&self, path: &PostPath) -> Option<&PostPath> {
+        self.redirects.get(path).map(|redirect| &redirect.target)
+    }
+
+    /// Every old path that redirects somewhere, sorted by path.
+    pub fn redirects(&self) -> Vec<(&PostPath, &Redirect)> {
+        let mut redirects: Vec<(&PostPath, &Redirect)> = self.redirects.iter().collect();
+        redirects.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        redirects
+    }
+
+    /// The posts closest to a route that does not exist, best match first.
+    pub fn suggest_posts(&self, missing_route: &str, limit: usize) -> Vec<&SuggestedPost> {
+        if missing_route.len() > MAX_SUGGESTION_ROUTE_LEN {
+            return Vec::new();
+        }
+
+        let mut scored_posts: Vec<(f64, &SuggestedPost)> = self
+            .suggestion_candidates
+            .iter()
+            .map(|post| (suggestion_score(missing_route, post), post))
+            .filter(|(score, _)| *score >= MIN_SUGGESTION_SCORE)
+            .collect();
+        scored_posts.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        scored_posts
+            .into_iter()
+            .take(limit)
+            .map(|(_, post)| post)
+            .collect()
+    }
+
+    pub async fn get_all_posts(&self) -> Vec<Post> {
+        self.db
+            .query(
+                "SELECT * FROM posts WHERE metadata.is_draft = false ORDER BY metadata.date DESC",
+            )
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap_or_default()
+    }
+
+    pub async fn query_news(&self, params: &NewsParams) -> NewsPage {
+        let page = params.page();
+        let page_size = params.page_size();
+        let filters = "metadata.is_draft = false
+            AND ($tag = NONE OR metadata.tags CONTAINS $tag)
+            AND ($topic = NONE OR metadata.topic = $topic)
+            AND ($year = NONE OR time::year(metadata.date) = $year)
+            AND ($collection = NONE OR collection = $collection)";
+
+        let mut response = self
+            .db
+            .query(format!(
+                "SELECT * FROM posts WHERE {} ORDER BY metadata.date DESC, file_path ASC LIMIT $limit START $start",
+                filters
+            ))
+            .query(format!(
+                "SELECT count() AS total FROM posts WHERE {} GROUP ALL",
+                filters
+            ))
+            .bind(("tag", params.tag.clone()))
+            .bind(("topic", params.topic.clone()))
+            .bind(("year", params.year))
+            .bind(("collection", params.collection.clone()))
+            .bind(("limit", page_size))
+            .bind(("start", (page - 1) * page_size))
+            .await
+            .unwrap();
+
+        NewsPage {
+            posts: response.take(0).unwrap_or_default(),
+            page,
+            page_size,
+            total: response
+                .take::<Option<usize>>((1, "total"))
+                .unwrap_or_default()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub async fn get_news_facets(&self) -> NewsFacets {
+        let posts: Vec<FacetsQueryResult> = self
+            .db
+            .query(
+                "SELECT
+                    metadata.tags AS tags,
+                    metadata.topic AS topic,
+                    time::year(metadata.date) AS year
+                FROM posts
+                WHERE metadata.is_draft = false",
+            )
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap_or_default();
+
+        let mut tags = BTreeSet::new();
+        let mut topics = BTreeSet::new();
+        let mut years = BTreeSet::new();
+        for post in posts {
+            tags.extend(post.tags);
+            topics.extend(post.topic);
+            years.insert(post.year);
+        }
+
+        NewsFacets {
+            collections: self.collections.names(),
+            tags: tags.into_iter().collect(),
+            topics: topics.into_iter().collect(),
+            years: years.into_iter().rev().collect(),
+        }
+    }
+
+    /// Graph of every post, or of the posts of one collection. Links to posts
+    /// of other collections are left out along with them.
+    pub async fn get_overall_graph_data(&self, params: &GraphParams) -> GraphData {
+        let mut posts_result = self
+            .db
+            .query("SELECT * FROM posts WHERE $collection = NONE OR collection = $collection")
+            .bind(("collection", params.collection.clone()))
+            .await
+            .unwrap();
         let posts: Vec<Post> = posts_result.take(0).unwrap();
 
         let mut edges_result = self
@@ -191,8 +614,8 @@ impl PostsSearchEngine {
         GraphData { nodes, edges }
     }
 
-    pub async fn get_graph_from_related_posts(&self, main_node_file_path: &str) -> GraphData {
-        let curr_post = self.get_post(main_node_file_path).await;
+    pub async fn get_graph_from_related_posts(&self, main_node_path: &PostPath) -> GraphData {
+        let curr_post = self.get_post(main_node_path).await;
 
         if curr_post.is_none() {
             return GraphData::empty();
@@ -203,7 +626,7 @@ impl PostsSearchEngine {
         let mut result = self
             .db
             .query(query)
-            .bind(("file_path", main_node_file_path.to_string()))
+            .bind(("file_path", main_node_path.as_str().to_string()))
             .await
             .unwrap();
 
@@ -251,26 +674,525 @@ impl PostsSearchEngine {
     }
 }
 
+fn build_redirects(
+    posts: &[Post],
+    renames: &HashMap<String, String>,
+) -> HashMap<PostPath, Redirect> {
+    let is_published = |file_path: &str| {
+        posts
+            .iter()
+            .any(|post| post.file_path == file_path && !post.metadata.is_draft)
+    };
+
+    let mut redirects: HashMap<PostPath, Redirect> = HashMap::new();
+    for post in posts.iter().filter(|post| !post.metadata.is_draft) {
+        let Ok(target) = PostPath::from_file_path(&post.file_path) else {
+            continue;
+        };
+
+        for alias in &post.metadata.aliases {
+            let Ok(alias_path) = parse_alias(alias) else {
+                continue;
+            };
+            // A post that exists is always served, even if another one has its path as alias
+            if posts.iter().any(|p| p.file_path == alias_path.as_str()) {
+                eprintln!(
+                    "Ignoring alias {} of {}, a post already lives there",
+                    alias, post.file_path
+                );
+                continue;
+            }
+            if let Some(existing) = redirects.get(&alias_path) {
+                eprintln!(
+                    "Ignoring alias {} of {}, it already redirects to {}",
+                    alias, post.file_path, existing.target
+                );
+                continue;
+            }
+            redirects.insert(
+                alias_path,
+                Redirect {
+                    target: target.clone(),
+                    source: RedirectSource::Alias,
+                },
+            );
+        }
+    }
+
+    // Aliases are written by hand, so they win over what git history says
+    for (old_path, new_path) in renames {
+        if !is_published(new_path) || posts.iter().any(|post| &post.file_path == old_path) {
+            continue;
+        }
+        if let (Ok(old_path), Ok(target)) = (
+            PostPath::from_file_path(old_path),
+            PostPath::from_file_path(new_path),
+        ) {
+            redirects.entry(old_path).or_insert(Redirect {
+                target,
+                source: RedirectSource::GitRename,
+            });
+        }
+    }
+
+    redirects
+}
+
+/// Drops whatever the store had and defines the tables from scratch, the
+/// search indexes are then built again as the posts are inserted.
+async fn define_schema(db: &Surreal<Any>) -> Result<(), surrealdb::Error> {
+    // Every language has its own analyzer, so each one is searched on fields
+    // of its own that only the posts written in it fill
+    let language_schema: String = Language::ALL
+        .iter()
+        .map(|language| {
+            format!(
+                "REMOVE ANALYZER IF EXISTS {analyzer};
+                DEFINE FIELD {title} ON posts TYPE option<string>;
+                DEFINE FIELD {content} ON posts TYPE option<string>;
+                DEFINE ANALYZER {analyzer} TOKENIZERS class FILTERS lowercase, snowball({snowball}), ascii, edgengram(2, 15);
+                DEFINE INDEX {code}_title ON TABLE posts FIELDS {title} SEARCH ANALYZER {analyzer} BM25 HIGHLIGHTS;
+                DEFINE INDEX {code}_content ON TABLE posts FIELDS {content} SEARCH ANALYZER {analyzer} BM25 HIGHLIGHTS;
+                ",
+                analyzer = language.analyzer(),
+                snowball = language.snowball_name(),
+                code = language.code(),
+                title = language.title_field(),
+                content = language.content_field(),
+            )
+        })
+        .collect();
+
+    db.query(format!(
+        "REMOVE TABLE IF EXISTS snapshot;
+        REMOVE TABLE IF EXISTS points_to;
+        REMOVE TABLE IF EXISTS posts;
+        REMOVE ANALYZER IF EXISTS full_text_analyzer;
+
+        DEFINE TABLE snapshot SCHEMALESS;
+        DEFINE TABLE posts SCHEMAFULL;
+        DEFINE FIELD file_name ON posts TYPE string;
+        DEFINE FIELD file_path ON posts TYPE string;
+        DEFINE FIELD collection ON posts TYPE string;
+        DEFINE FIELD content_hash ON posts TYPE option<string>;
+        DEFINE FIELD metadata ON posts TYPE object;
+        DEFINE FIELD metadata.title ON posts TYPE string;
+        DEFINE FIELD metadata.description ON posts TYPE string;
+        DEFINE FIELD metadata.tags ON posts TYPE array<string>;
+        DEFINE FIELD metadata.date ON posts TYPE datetime;
+        DEFINE FIELD metadata.topic ON posts TYPE option<string>;
+        DEFINE FIELD metadata.post_source_url ON posts TYPE string;
+        DEFINE FIELD metadata.is_draft ON posts TYPE bool DEFAULT false;
+        DEFINE FIELD metadata.stats ON posts TYPE object;
+        DEFINE FIELD metadata.stats.words ON posts TYPE int;
+        DEFINE FIELD metadata.stats.code_blocks ON posts TYPE int;
+        DEFINE FIELD metadata.stats.code_lines ON posts TYPE int;
+        DEFINE FIELD metadata.stats.images ON posts TYPE int;
+        DEFINE FIELD metadata.stats.links ON posts TYPE int;
+        DEFINE FIELD metadata.stats.headings ON posts TYPE array<object> DEFAULT [];
+        DEFINE FIELD metadata.stats.headings.*.depth ON posts TYPE int;
+        DEFINE FIELD metadata.stats.headings.*.text ON posts TYPE string;
+        DEFINE FIELD metadata.aliases ON posts TYPE array<string> DEFAULT [];
+        DEFINE FIELD metadata.revisions ON posts TYPE array<object> DEFAULT [];
+        DEFINE FIELD metadata.revisions.*.hash ON posts TYPE string;
+        DEFINE FIELD metadata.revisions.*.date ON posts TYPE datetime;
+        DEFINE FIELD metadata.revisions.*.message ON posts TYPE string;
+        DEFINE FIELD metadata.lang ON posts TYPE string DEFAULT 'en';
+        DEFINE FIELD metadata.translations ON posts TYPE array<string> DEFAULT [];
+        DEFINE FIELD metadata.pinned ON posts TYPE bool DEFAULT false;
+        DEFINE FIELD metadata.weight ON posts TYPE option<int>;
+        DEFINE FIELD content ON posts TYPE string;
+        DEFINE INDEX file_path_index ON TABLE posts COLUMNS file_path UNIQUE;
+
+        {}",
+        language_schema
+    ))
+    .await?
+    .check()?;
+
+    Ok(())
+}
+
+/// A post as it is stored, along with its content hash and what it is
+/// searched by, in the fields of its language.
+#[derive(Serialize)]
+struct IndexedPost {
+    #[serde(flatten)]
+    post: Post,
+    content_hash: String,
+    #[serde(flatten)]
+    search_fields: HashMap<String, String>,
+}
+
+impl IndexedPost {
+    fn new(post: Post) -> IndexedPost {
+        let lang = post.metadata.lang;
+        let search_fields = HashMap::from([
+            (
+                lang.title_field(),
+                lang.remove_stopwords(&post.metadata.title),
+            ),
+            (lang.content_field(), lang.remove_stopwords(&post.content)),
+        ]);
+
+        IndexedPost {
+            content_hash: content_hash(&post.file_path, &post.content),
+            search_fields,
+            post,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct IndexSnapshot {
+    version: u32,
+    settings_hash: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IndexedHash {
+    file_path: String,
+    content_hash: Option<String>,
+}
+
 #[derive(Deserialize)]
 struct QueryRelatedPostResult {
     related_posts: Vec<Post>,
 }
 
-fn get_mentioned_posts_in_post_content(post: &Post) -> Vec<String> {
+#[derive(Deserialize)]
+struct FacetsQueryResult {
+    tags: Vec<String>,
+    topic: Option<String>,
+    year: i32,
+}
+
+fn get_mentioned_posts_in_post_content(
+    post: &Post,
+    file_paths: &[&str],
+    redirects: &HashMap<PostPath, Redirect>,
+) -> Vec<String> {
     let content = post.content.to_owned();
     let regex_pattern = Regex::new(r"\[([^\]]+)\]\(([^\)]+)\)").unwrap();
 
-    let links: Vec<String> = regex_pattern
+    let mut links: Vec<String> = regex_pattern
         .captures_iter(&content)
         .filter_map(|cap| {
             let link = &cap[2];
             if link.starts_with("http") || link.starts_with("https") {
-                None
-            } else {
-                Some(format!("{}.md", link))
+                return None;
+            }
+
+            let file_path = format!("{}.md", link);
+            if file_paths.contains(&file_path.as_str()) {
+                return Some(file_path);
+            }
+            // Links to the old path of a moved post still point to it in the graph
+            match PostPath::from_route(link)
+                .ok()
+                .and_then(|path| redirects.get(&path))
+            {
+                Some(redirect) => Some(redirect.target.as_str().to_string()),
+                None => Some(file_path),
             }
         })
         .collect();
 
+    // Transcluded notes are shown inside the post, so they count as links too
+    let embed_pattern = Regex::new(NOTE_EMBED_PATTERN).unwrap();
+    for cap in embed_pattern.captures_iter(&content) {
+        if let Some(embedded) = resolve_note_reference(&cap[1], file_paths.iter().copied())
+            && !links.contains(&embedded)
+        {
+            links.push(embedded);
+        }
+    }
+
     links
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Collection, Visibility};
+    use std::path::{Path, PathBuf};
+
+    fn test_garden(name: &str) -> PathBuf {
+        let garden = std::env::temp_dir().join(format!("guslee-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&garden);
+        fs::create_dir_all(garden.join("journal")).unwrap();
+        fs::write(
+            garden.join("welcome.md"),
+            "---\ntitle: Welcome\ndescription: Hi\ntags: [intro]\ndate: 2025-09-15\n---\nSee [Rathmines](journal/rathmines)\n",
+        )
+        .unwrap();
+        fs::write(
+            garden.join("journal/rathmines.md"),
+            "---\ntitle: Rathmines\ndescription: Where I live\ntags: []\ndate: 2025-10-01\naliases: [rathmines]\n---\nHello\n",
+        )
+        .unwrap();
+
+        garden
+    }
+
+    fn garden_collection(garden: &Path) -> Collections {
+        Collections::new(vec![Collection::new("garden", garden)])
+    }
+
+    #[tokio::test]
+    async fn test_indexes_garden_in_memory() {
+        let garden = test_garden("memory");
+        let engine =
+            PostsSearchEngine::new("", &garden_collection(&garden), &DatabaseConfig::default())
+                .await
+                .unwrap();
+
+        let welcome = PostPath::from_file_path("welcome.md").unwrap();
+        assert_eq!(
+            engine.get_post(&welcome).await.unwrap().metadata.title,
+            "Welcome"
+        );
+        assert_eq!(
+            engine
+                .get_graph_from_related_posts(&welcome)
+                .await
+                .edges
+                .len(),
+            1
+        );
+
+        let old_path = PostPath::from_route("rathmines").unwrap();
+        assert_eq!(
+            engine.redirect_for(&old_path).map(PostPath::as_str),
+            Some("journal/rathmines.md")
+        );
+
+        fs::remove_dir_all(garden).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_skips_posts_with_invalid_metadata() {
+        let garden = test_garden("invalid");
+        fs::write(
+            garden.join("broken.md"),
+            "---\ntitle: Broken\ndescription: Bad date\ntags: []\ndate: someday\n---\nHello\n",
+        )
+        .unwrap();
+
+        let engine =
+            PostsSearchEngine::new("", &garden_collection(&garden), &DatabaseConfig::default())
+                .await
+                .unwrap();
+        let mut titles: Vec<String> = engine
+            .get_all_posts()
+            .await
+            .into_iter()
+            .map(|post| post.metadata.title)
+            .collect();
+        titles.sort();
+        assert_eq!(titles, ["Rathmines", "Welcome"]);
+
+        fs::remove_dir_all(garden).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_finds_attachments_of_drafts() {
+        let garden = test_garden("attachments");
+        fs::create_dir_all(garden.join("drafts")).unwrap();
+        fs::write(
+            garden.join("drafts/idea.md"),
+            "---\ntitle: Idea\ndescription: Not yet\ntags: []\ndate: 2025-10-02\nis_draft: true\n---\nSoon\n",
+        )
+        .unwrap();
+
+        let engine =
+            PostsSearchEngine::new("", &garden_collection(&garden), &DatabaseConfig::default())
+                .await
+                .unwrap();
+        assert!(engine.is_draft_attachment("drafts/sketch.png").await);
+        assert!(!engine.is_draft_attachment("journal/cover.png").await);
+        assert!(!engine.is_draft_attachment("banner.png").await);
+        assert!(!engine.is_draft_attachment("photos/cat.png").await);
+
+        fs::remove_dir_all(garden).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_indexes_nested_collections() {
+        let garden = test_garden("collections");
+        fs::create_dir_all(garden.join("drafts")).unwrap();
+        fs::write(
+            garden.join("drafts/idea.md"),
+            "---\ntitle: Idea\ndescription: Not yet\ntags: []\ndate: 2025-10-02\n---\nSoon\n",
+        )
+        .unwrap();
+        let collections = Collections::new(vec![
+            Collection::new("notes", &garden),
+            Collection {
+                prefix: String::from("journal"),
+                ..Collection::new("journal", garden.join("journal"))
+            },
+            Collection {
+                prefix: String::from("ideas"),
+                default_visibility: Visibility::Draft,
+                ..Collection::new("drafts", garden.join("drafts"))
+            },
+        ]);
+        let engine = PostsSearchEngine::new("", &collections, &DatabaseConfig::default())
+            .await
+            .unwrap();
+
+        // Nested roots belong to their own collection, the urls stay the same
+        let rathmines = PostPath::from_file_path("journal/rathmines.md").unwrap();
+        assert_eq!(
+            engine.get_post(&rathmines).await.unwrap().collection,
+            "journal"
+        );
+        assert_eq!(engine.get_all_posts().await.len(), 2);
+
+        let journal = NewsParams {
+            collection: Some(String::from("journal")),
+            ..Default::default()
+        };
+        assert_eq!(engine.query_news(&journal).await.total, 1);
+        let graph = engine
+            .get_overall_graph_data(&GraphParams {
+                collection: Some(String::from("notes")),
+            })
+            .await;
+        assert_eq!((graph.nodes.len(), graph.edges.len()), (1, 0));
+
+        fs::remove_dir_all(garden).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_searches_each_language_with_its_analyzer() {
+        let garden = test_garden("languages");
+        fs::write(
+            garden.join("journal/rathmines_es.md"),
+            "---\ntitle: Rathmines\ndescription: Donde vivo\ntags: []\ndate: 2025-10-01\nlang: es\ntranslations: [journal/rathmines]\n---\nLas casas de ladrillo del barrio\n",
+        )
+        .unwrap();
+        let engine =
+            PostsSearchEngine::new("", &garden_collection(&garden), &DatabaseConfig::default())
+                .await
+                .unwrap();
+        let search = |query: &str, lang: Option<Language>| Params {
+            query: Some(query.to_string()),
+            limit: None,
+            collection: None,
+            lang,
+        };
+
+        // Stemmed, so the singular finds the plural
+        let found = engine.query_posts(&search("casa", None)).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].metadata.lang, Language::Spanish);
+        // Only stopwords of the post language are dropped
+        assert!(engine.query_posts(&search("las", None)).await.is_empty());
+        let spanish = engine
+            .query_posts(&search("rathmines", Some(Language::Spanish)))
+            .await;
+        assert_eq!(spanish.len(), 1);
+        assert_eq!(spanish[0].file_path, "journal/rathmines_es.md");
+
+        // Listed on one side only, but linked both ways
+        let rathmines = engine
+            .get_post(&PostPath::from_file_path("journal/rathmines.md").unwrap())
+            .await
+            .unwrap();
+        let translations = engine.get_translations(&rathmines).await;
+        assert_eq!(translations.len(), 1);
+        assert_eq!(translations[0].file_path, "journal/rathmines_es.md");
+
+        fs::remove_dir_all(garden).unwrap();
+    }
+
+    #[test]
+    fn test_collection_required_fields() {
+        let collection = Collection {
+            required_fields: vec![String::from("topic")],
+            ..Collection::new("gaming", "./gaming")
+        };
+        let content = "---\ntitle: Peak\ndescription: Review\ntags: []\ndate: 2025-10-02\n---\n";
+
+        assert!(matches!(
+            extract_full_metadata("", "./gaming/peak.md", content, &collection),
+            Err(utils::MetadataError::MissingField(field)) if field == "topic"
+        ));
+    }
+
+    // Each run gets its own runtime, so the store is closed like on a restart
+    fn index_in_own_runtime(collections: &Collections, db_config: &DatabaseConfig) -> Vec<String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let engine = PostsSearchEngine::new("", collections, db_config)
+                .await
+                .unwrap();
+            let welcome = PostPath::from_file_path("welcome.md").unwrap();
+            let graph = engine.get_graph_from_related_posts(&welcome).await;
+
+            let mut titles: Vec<String> = engine
+                .get_all_posts()
+                .await
+                .into_iter()
+                .map(|post| post.metadata.title)
+                .collect();
+            titles.sort();
+            titles.push(format!("{} edges", graph.edges.len()));
+            titles
+        })
+    }
+
+    #[test]
+    fn test_updates_snapshot_on_disk_store() {
+        let garden = test_garden("disk");
+        let mut db_config = DatabaseConfig {
+            endpoint: format!("surrealkv://{}", garden.join(".db").display()),
+            credentials: None,
+            force_rebuild: false,
+        };
+        let collections = garden_collection(&garden);
+
+        assert_eq!(
+            index_in_own_runtime(&collections, &db_config),
+            ["Rathmines", "Welcome", "1 edges"]
+        );
+        // Nothing changed, everything comes from the snapshot
+        assert_eq!(
+            index_in_own_runtime(&collections, &db_config),
+            ["Rathmines", "Welcome", "1 edges"]
+        );
+
+        fs::remove_file(garden.join("journal/rathmines.md")).unwrap();
+        fs::write(
+            garden.join("welcome.md"),
+            "---\ntitle: Hello again\ndescription: Hi\ntags: []\ndate: 2025-09-15\n---\nSee [Rathmines](journal/rathmines)\n",
+        )
+        .unwrap();
+        assert_eq!(
+            index_in_own_runtime(&collections, &db_config),
+            ["Hello again", "0 edges"]
+        );
+
+        db_config.force_rebuild = true;
+        assert_eq!(
+            index_in_own_runtime(&collections, &db_config),
+            ["Hello again", "0 edges"]
+        );
+
+        // Nothing changed on disk, but the posts now default to drafts
+        db_config.force_rebuild = false;
+        let mut drafts = Collection::new("garden", &garden);
+        drafts.default_visibility = Visibility::Draft;
+        assert_eq!(
+            index_in_own_runtime(&Collections::new(vec![drafts]), &db_config),
+            ["0 edges"]
+        );
+
+        fs::remove_dir_all(garden).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+use markdown::{
+    Constructs, ParseOptions,
+    mdast::{Heading, Node},
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::utils::NOTE_EMBED_PATTERN;
+
+static NOTE_EMBED: OnceLock<Regex> = OnceLock::new();
+
+/// What a post is made of, measured on its markdown. Only prose counts as
+/// words: frontmatter, code, math, raw html and bare urls are left out.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContentStats {
+    pub words: u32,
+    pub code_blocks: u32,
+    pub code_lines: u32,
+    pub images: u32,
+    pub links: u32,
+    pub headings: Vec<OutlineHeading>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OutlineHeading {
+    pub depth: u8,
+    pub text: String,
+}
+
+/// How fast posts are read. Code is read line by line, much slower than prose.
+#[derive(Clone, Copy, Debug)]
+pub struct ReadingSpeed {
+    pub prose_words_per_minute: u32,
+    pub code_lines_per_minute: u32,
+}
+
+impl Default for ReadingSpeed {
+    fn default() -> Self {
+        ReadingSpeed {
+            prose_words_per_minute: 230,
+            code_lines_per_minute: 60,
+        }
+    }
+}
+
+impl ContentStats {
+    pub fn from_markdown(content: &str) -> ContentStats {
+        let options = ParseOptions {
+            constructs: Constructs {
+                frontmatter: true,
+                math_text: true,
+                math_flow: true,
+                ..Constructs::gfm()
+            },
+            ..Default::default()
+        };
+
+        let mut stats = ContentStats::default();
+        // Only MDX constructs can fail to parse, and those are never enabled
+        if let Ok(tree) = markdown::to_mdast(content, &options) {
+            stats.visit(&tree);
+        }
+        stats
+    }
+
+    /// Minutes it takes to read the post, at least one.
+    pub fn reading_time(&self, speed: &ReadingSpeed) -> u32 {
+        let minutes = self.words as f64 / speed.prose_words_per_minute.max(1) as f64
+            + self.code_lines as f64 / speed.code_lines_per_minute.max(1) as f64;
+        (minutes.ceil() as u32).max(1)
+    }
+
+    fn visit(&mut self, node: &Node) {
+        match node {
+            Node::Yaml(_)
+            | Node::Toml(_)
+            | Node::Html(_)
+            | Node::InlineCode(_)
+            | Node::InlineMath(_)
+            | Node::Math(_)
+            | Node::Definition(_) => return,
+            Node::Code(code) => {
+                self.code_blocks += 1;
+                self.code_lines += code.value.lines().count() as u32;
+                return;
+            }
+            Node::Image(_) | Node::ImageReference(_) => self.images += 1,
+            Node::Link(_) | Node::LinkReference(_) => self.links += 1,
+            Node::Heading(heading) => self.headings.push(OutlineHeading {
+                depth: heading.depth,
+                text: heading_text(heading),
+            }),
+            Node::Text(text) => self.words += count_words(&text.value),
+            _ => {}
+        }
+
+        for child in node.children().into_iter().flatten() {
+            self.visit(child);
+        }
+    }
+}
+
+// Urls and `![[note]]` embeds are not read, the embedded note has its own stats
+fn count_words(text: &str) -> u32 {
+    let note_embed = NOTE_EMBED.get_or_init(|| Regex::new(NOTE_EMBED_PATTERN).unwrap());
+
+    note_embed
+        .replace_all(text, " ")
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .filter(|word| !word.contains("://") && !word.starts_with("www."))
+        .count() as u32
+}
+
+fn heading_text(heading: &Heading) -> String {
+    fn collect(node: &Node, text: &mut String) {
+        match node {
+            Node::Text(t) => text.push_str(&t.value),
+            Node::InlineCode(code) => text.push_str(&code.value),
+            _ => node
+                .children()
+                .into_iter()
+                .flatten()
+                .for_each(|child| collect(child, text)),
+        }
+    }
+
+    let mut text = String::new();
+    heading
+        .children
+        .iter()
+        .for_each(|child| collect(child, &mut text));
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POST: &str = "---
+title: Peak
+description: Review
+tags: [gaming, steam]
+date: 2025-09-15
+---
+# Peak review
+
+A short game about climbing, see [the store](https://store.steampowered.com) or https://example.com.
+
+![Mesa](mesa.png)
+
+## Setup `v1.2`
+
+```rust
+fn main() {
+    println!(\"climb\");
+}
+```
+
+![[notes/mesa]]
+";
+
+    #[test]
+    fn test_counts_only_prose() {
+        let stats = ContentStats::from_markdown(POST);
+
+        // The frontmatter, the code, the url and the embed are left out
+        assert_eq!(stats.words, 12);
+        assert_eq!(stats.code_blocks, 1);
+        assert_eq!(stats.code_lines, 3);
+        assert_eq!(stats.images, 1);
+        assert_eq!(stats.links, 2);
+        assert_eq!(
+            stats.headings,
+            vec![
+                OutlineHeading {
+                    depth: 1,
+                    text: String::from("Peak review")
+                },
+                OutlineHeading {
+                    depth: 2,
+                    text: String::from("Setup v1.2")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reading_time_does_not_overflow() {
+        let stats = ContentStats {
+            words: 100_000,
+            code_lines: 600,
+            ..Default::default()
+        };
+        let speed = ReadingSpeed {
+            prose_words_per_minute: 200,
+            code_lines_per_minute: 30,
+        };
+
+        assert_eq!(stats.reading_time(&speed), 520);
+        assert_eq!(ContentStats::default().reading_time(&speed), 1);
+    }
+}
@@ -1,4 +1,32 @@
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::language::Language;
+use crate::utils::{Post, PostPath};
+
+/// Where the engine keeps the indexed posts. The endpoint is anything
+/// SurrealDB connects to: `mem://`, `surrealkv://path/to/dir` for on-disk
+/// storage, or a `ws://` / `http://` server.
+#[derive(Clone, Debug)]
+pub struct DatabaseConfig {
+    pub endpoint: String,
+    /// Root user and password, for servers that do not run unauthenticated.
+    pub credentials: Option<(String, String)>,
+    /// Ignores the index snapshot kept in the store and indexes every post.
+    pub force_rebuild: bool,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> DatabaseConfig {
+        DatabaseConfig {
+            endpoint: String::from("mem://"),
+            credentials: None,
+            force_rebuild: false,
+        }
+    }
+}
 
 #[derive(Deserialize, Clone)]
 #[serde(untagged)]
@@ -11,9 +39,184 @@ pub enum Limit {
 pub struct Params {
     pub query: Option<String>,
     pub limit: Option<Limit>,
+    pub collection: Option<String>,
+    pub lang: Option<Language>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct GraphParams {
+    pub collection: Option<String>,
+}
+
+/// A folder of posts indexed under its own name. Its posts are served under
+/// `/posts/{prefix}/...`, so folders can be moved around on disk without
+/// changing any url as long as the prefix stays the same.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Collection {
+    pub name: String,
+    /// Folder the posts are read from.
+    pub root: PathBuf,
+    /// Path the posts are served under, empty to serve them from the top.
+    #[serde(default)]
+    pub prefix: String,
+    /// Visibility of the posts that don't set `is_draft` in their frontmatter.
+    #[serde(default)]
+    pub default_visibility: Visibility,
+    /// Language of the posts that don't set `lang` in their frontmatter.
+    #[serde(default)]
+    pub default_language: Language,
+    /// Frontmatter fields every post of the collection must have, on top of
+    /// the ones all posts need.
+    #[serde(default)]
+    pub required_fields: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Public,
+    Draft,
+}
+
+/// How posts that are not pinned are ordered in listings.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PostSort {
+    /// Newest first.
+    #[default]
+    Date,
+    Title,
+    /// By the `weight` of the posts, lightest first. Posts without one go
+    /// after them, newest first.
+    Manual,
+}
+
+impl Collection {
+    pub fn new(name: &str, root: impl Into<PathBuf>) -> Collection {
+        Collection {
+            name: name.to_string(),
+            root: root.into(),
+            prefix: String::new(),
+            default_visibility: Visibility::Public,
+            default_language: Language::English,
+            required_fields: Vec::new(),
+        }
+    }
+
+    /// Garden path of a file of the collection, from its path inside `root`.
+    pub fn file_path(&self, relative_path: &str) -> String {
+        match self.prefix.trim_matches('/') {
+            "" => relative_path.to_string(),
+            prefix => format!("{}/{}", prefix, relative_path),
+        }
+    }
+}
+
+/// A file found in one of the collections.
+pub struct CollectionFile<'a> {
+    pub collection: &'a Collection,
+    /// Path of the file inside its collection's root.
+    pub relative_path: String,
+    /// Path of the file in the garden, which is also its url.
+    pub file_path: String,
+    pub disk_path: PathBuf,
+}
+
+/// Every configured collection. A garden path belongs to the collection with
+/// the longest prefix it starts with.
+#[derive(Clone, Debug)]
+pub struct Collections(Vec<Collection>);
+
+impl Collections {
+    pub fn new(collections: Vec<Collection>) -> Collections {
+        Collections(collections)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Collection> {
+        self.0.iter()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|collection| collection.name.clone())
+            .collect()
+    }
+
+    /// The collection a garden path belongs to, along with the path of the
+    /// file inside the collection's root.
+    pub fn resolve<'a>(&self, file_path: &'a str) -> Option<(&Collection, &'a str)> {
+        self.0
+            .iter()
+            .filter_map(|collection| match collection.prefix.trim_matches('/') {
+                "" => Some((collection, file_path, 0)),
+                prefix => file_path
+                    .strip_prefix(prefix)
+                    .and_then(|rest| rest.strip_prefix('/'))
+                    .map(|rest| (collection, rest, prefix.len())),
+            })
+            .max_by_key(|(_, _, prefix_len)| *prefix_len)
+            .map(|(collection, relative_path, _)| (collection, relative_path))
+    }
+
+    /// Where a garden path (of a post or an attachment) is on disk.
+    pub fn disk_path(&self, file_path: &str) -> Option<PathBuf> {
+        self.resolve(file_path)
+            .map(|(collection, relative_path)| collection.root.join(relative_path))
+    }
+
+    /// Every file of every collection. Folders that are the root of another
+    /// collection are left to that one, so collections can be nested.
+    pub fn files(&self) -> Vec<CollectionFile<'_>> {
+        let roots: Vec<PathBuf> = self
+            .0
+            .iter()
+            .filter_map(|collection| fs::canonicalize(&collection.root).ok())
+            .collect();
+        let is_other_root = |root: &Path, dir: &Path| {
+            dir != root && fs::canonicalize(dir).is_ok_and(|dir| roots.contains(&dir))
+        };
+
+        let mut files = Vec::new();
+        for collection in &self.0 {
+            let walker = WalkDir::new(&collection.root)
+                .into_iter()
+                .filter_entry(|entry| {
+                    !(entry.file_type().is_dir() && is_other_root(&collection.root, entry.path()))
+                });
+            for entry in walker {
+                let entry = entry.expect("Error while accessing the WalkDir entry");
+                if !entry.path().is_file() {
+                    continue;
+                }
+                let Ok(relative_path) = entry.path().strip_prefix(&collection.root) else {
+                    continue;
+                };
+                let relative_path = relative_path.to_string_lossy().to_string();
+
+                files.push(CollectionFile {
+                    collection,
+                    file_path: collection.file_path(&relative_path),
+                    relative_path,
+                    disk_path: entry.path().to_path_buf(),
+                });
+            }
+        }
+
+        files
+    }
+}
+
+impl Default for Collections {
+    fn default() -> Collections {
+        Collections(vec![Collection::new("garden", "./garden")])
+    }
 }
 
 pub const DEFAULT_SEARCH_LIMIT: Limit = Limit::Number(100);
+pub const DEFAULT_NEWS_PAGE_SIZE: usize = 10;
+pub const MAX_NEWS_PAGE_SIZE: usize = 50;
 
 impl Limit {
     pub fn value(&self) -> usize {
@@ -24,6 +227,64 @@ impl Limit {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct NewsParams {
+    pub tag: Option<String>,
+    pub topic: Option<String>,
+    pub year: Option<i32>,
+    pub collection: Option<String>,
+    pub page: Option<usize>,
+    pub page_size: Option<usize>,
+}
+
+impl NewsParams {
+    pub fn page(&self) -> usize {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+            .unwrap_or(DEFAULT_NEWS_PAGE_SIZE)
+            .clamp(1, MAX_NEWS_PAGE_SIZE)
+    }
+
+    pub fn has_filters(&self) -> bool {
+        self.tag.is_some()
+            || self.topic.is_some()
+            || self.year.is_some()
+            || self.collection.is_some()
+    }
+}
+
+pub struct NewsPage {
+    pub posts: Vec<Post>,
+    pub page: usize,
+    pub page_size: usize,
+    pub total: usize,
+}
+
+impl NewsPage {
+    pub fn total_pages(&self) -> usize {
+        self.total.div_ceil(self.page_size).max(1)
+    }
+
+    pub fn has_previous(&self) -> bool {
+        self.page > 1
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.page < self.total_pages()
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct NewsFacets {
+    pub collections: Vec<String>,
+    pub tags: Vec<String>,
+    pub topics: Vec<String>,
+    pub years: Vec<i32>,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct GraphData {
     pub nodes: Vec<GraphNode>,
@@ -57,3 +318,63 @@ pub struct EdgeFilePath {
     pub source: String,
     pub target: String,
 }
+
+/// A post rendered to HTML, along with what was derived from its structure.
+/// The engine keeps these cached so posts are not rendered on every request.
+#[derive(Clone, Debug)]
+pub struct RenderedPost {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+    /// Plain text of the first paragraph, used in previews.
+    pub excerpt: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TocEntry {
+    pub depth: u8,
+    pub text: String,
+    pub id: String,
+}
+
+/// What the 404 page compares a missing route against, kept by the engine
+/// so a missing page does not load every post.
+#[derive(Clone, Debug)]
+pub struct SuggestedPost {
+    pub path: PostPath,
+    pub title: String,
+    pub description: String,
+}
+
+impl SuggestedPost {
+    pub fn new(post: &Post) -> Option<SuggestedPost> {
+        Some(SuggestedPost {
+            path: PostPath::from_file_path(&post.file_path).ok()?,
+            title: post.metadata.title.clone(),
+            description: post.metadata.description.clone(),
+        })
+    }
+}
+
+/// Where an old post path now leads, and why it redirects there.
+#[derive(Clone, Debug)]
+pub struct Redirect {
+    pub target: PostPath,
+    pub source: RedirectSource,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedirectSource {
+    /// Listed in the `aliases` frontmatter of the post.
+    Alias,
+    /// The post was renamed or moved in a git commit.
+    GitRename,
+}
+
+impl RedirectSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RedirectSource::Alias => "alias",
+            RedirectSource::GitRename => "git rename",
+        }
+    }
+}
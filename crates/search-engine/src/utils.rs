@@ -1,11 +1,43 @@
-use std::fs;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
 
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use gray_matter::Matter;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::IgnoredAny};
+use sha2::{Digest, Sha256};
+
+use crate::language::Language;
+use crate::stats::ContentStats;
+use crate::types::{Collection, Collections, PostSort, SuggestedPost, Visibility};
 
 pub struct TitleField;
 pub struct TagsField;
 
+// Formats accepted in the `date` frontmatter field. Full datetimes are tried
+// first, plain dates are stored as midnight UTC.
+const ACCEPTED_DATETIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+const ACCEPTED_DATE_FORMATS: [&str; 8] = [
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%d/%m/%Y",
+    "%d-%m-%Y",
+    "%d %b %Y",
+    "%d %B %Y",
+    "%b %d, %Y",
+    "%B %d, %Y",
+];
+
+/// Obsidian-style embed of another note: `![[note]]`, `![[note#Heading]]`
+/// or `![[note|alias]]`. The first group is the note, the second the heading.
+pub const NOTE_EMBED_PATTERN: &str = r"!\[\[([^\]\|#]+)(?:#([^\]\|]+))?(?:\|[^\]]*)?\]\]";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PostMetadata {
     pub title: String,
@@ -13,14 +45,22 @@ pub struct PostMetadata {
     pub description: String,
     pub tags: Vec<String>,
     pub date: String,
+    pub is_draft: Option<bool>,
     #[serde(default)]
-    pub is_draft: bool,
+    pub aliases: Vec<String>,
+    pub lang: Option<String>,
+    #[serde(default)]
+    pub translations: Vec<String>,
+    pub pinned: Option<bool>,
+    pub weight: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Post {
     pub file_name: String,
     pub file_path: String,
+    /// Name of the collection the post belongs to.
+    pub collection: String,
     pub metadata: MdMetadata,
     pub content: String,
 }
@@ -31,49 +71,668 @@ pub struct MdMetadata {
     pub topic: Option<String>,
     pub description: String,
     pub tags: Vec<String>,
-    pub date: String,
+    #[serde(with = "surreal_datetime")]
+    pub date: DateTime<Utc>,
     #[serde(default)]
     pub is_draft: bool,
     pub post_source_url: String,
-    pub reading_time: u8,
+    #[serde(default)]
+    pub stats: ContentStats,
+    /// Old routes of the post (`journal/rathmines`), they redirect to it.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Commits that changed the post, newest first. Empty when the garden is
+    /// not inside a git repository.
+    #[serde(default)]
+    pub revisions: Vec<Revision>,
+    #[serde(default)]
+    pub lang: Language,
+    /// Paths of the same post in other languages (`journal/rathmines_es.md`).
+    #[serde(default)]
+    pub translations: Vec<String>,
+    /// Listed before the other posts, after the ones pinned in the config.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Position of the post among the pinned ones, or in the manual sort.
+    pub weight: Option<i32>,
+}
+
+impl MdMetadata {
+    /// When the post was first committed.
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.revisions.last().map(|revision| revision.date)
+    }
+
+    /// When the post was last changed in a commit.
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.revisions.first().map(|revision| revision.date)
+    }
+
+    /// The last commit that touched the post, or its date outside of git.
+    pub fn last_changed(&self) -> DateTime<Utc> {
+        self.updated_at().unwrap_or(self.date)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Revision {
+    pub hash: String,
+    #[serde(with = "surreal_datetime")]
+    pub date: DateTime<Utc>,
+    pub message: String,
+}
+
+impl Revision {
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..self.hash.len().min(7)]
+    }
+}
+
+#[derive(Debug)]
+pub enum MetadataError {
+    InvalidFrontmatter(String),
+    InvalidDate(String),
+    InvalidAlias(String),
+    MissingField(String),
+    InvalidLanguage(String),
+    InvalidTranslation(String),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::InvalidFrontmatter(e) => write!(f, "invalid frontmatter: {}", e),
+            MetadataError::InvalidDate(date) => write!(
+                f,
+                "invalid date '{}', expected one of: {}",
+                date,
+                [
+                    ACCEPTED_DATE_FORMATS.as_slice(),
+                    ACCEPTED_DATETIME_FORMATS.as_slice()
+                ]
+                .concat()
+                .join(", ")
+            ),
+            MetadataError::InvalidAlias(alias) => write!(
+                f,
+                "invalid alias '{}', expected a path inside the garden like 'journal/rathmines'",
+                alias
+            ),
+            MetadataError::MissingField(field) => {
+                write!(f, "missing '{}', the collection requires it", field)
+            }
+            MetadataError::InvalidLanguage(lang) => write!(
+                f,
+                "unsupported lang '{}', expected one of: {}",
+                lang,
+                Language::ALL.map(|language| language.code()).join(", ")
+            ),
+            MetadataError::InvalidTranslation(translation) => write!(
+                f,
+                "invalid translation '{}', expected a path inside the garden like 'journal/rathmines_es'",
+                translation
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+/// Path of a post inside the garden (`gaming/expedition33/review.md`). It can
+/// only be built from relative paths made of plain segments, so it never
+/// points outside of the garden, and it is what every post lookup takes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PostPath(String);
+
+#[derive(Debug)]
+pub enum PostPathError {
+    Empty,
+    Absolute,
+    InvalidSegment(String),
+    NotMarkdown,
+}
+
+impl fmt::Display for PostPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostPathError::Empty => write!(f, "empty post path"),
+            PostPathError::Absolute => write!(f, "post paths must be relative to the garden"),
+            PostPathError::InvalidSegment(segment) => {
+                write!(f, "invalid path segment '{}'", segment)
+            }
+            PostPathError::NotMarkdown => write!(f, "posts must be markdown files"),
+        }
+    }
+}
+
+impl std::error::Error for PostPathError {}
+
+impl PostPath {
+    /// Parses the path of a post as used in urls, without the `.md` extension.
+    pub fn from_route(route: &str) -> Result<PostPath, PostPathError> {
+        PostPath::from_file_path(&format!("{}.md", route))
+    }
+
+    /// Parses the path of a post file relative to the garden root.
+    pub fn from_file_path(file_path: &str) -> Result<PostPath, PostPathError> {
+        if file_path.is_empty() || file_path == ".md" {
+            return Err(PostPathError::Empty);
+        }
+        if file_path.starts_with('/') || file_path.starts_with('\\') {
+            return Err(PostPathError::Absolute);
+        }
+        if let Some(segment) = file_path.split('/').find(|segment| {
+            matches!(*segment, "" | "." | "..") || segment.contains(['\\', ':', '\0'])
+        }) {
+            return Err(PostPathError::InvalidSegment(segment.to_string()));
+        }
+        if !file_path.ends_with(".md") {
+            return Err(PostPathError::NotMarkdown);
+        }
+
+        Ok(PostPath(file_path.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The path used in urls, without the `.md` extension.
+    pub fn route(&self) -> &str {
+        self.0.strip_suffix(".md").unwrap_or(&self.0)
+    }
 }
 
-pub fn extract_full_metadata(repo_source: &str, post_path: &str) -> Option<MdMetadata> {
+impl fmt::Display for PostPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// (De)serializes dates as SurrealDB datetimes instead of plain strings, so
+/// they can be compared and sorted inside queries.
+pub mod surreal_datetime {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        date: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        surrealdb::Datetime::from(*date).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        DateTime::<Utc>::deserialize(deserializer)
+    }
+}
+
+pub fn extract_full_metadata(
+    repo_source: &str,
+    post_path: &str,
+    content: &str,
+    collection: &Collection,
+) -> Result<MdMetadata, MetadataError> {
     use gray_matter::engine::YAML;
     let matter = Matter::<YAML>::new();
-    let content = fs::read_to_string(post_path).ok()?;
-
-    let post_metadata = match matter.parse::<PostMetadata>(&content) {
-        Ok(result) => result.data,
-        Err(e) => {
-            eprintln!("Failed to parse frontmatter: {:?}", e);
-            eprintln!("Content: {}", content);
-            None
+
+    if !collection.required_fields.is_empty() {
+        let fields = matter
+            .parse::<HashMap<String, IgnoredAny>>(content)
+            .ok()
+            .and_then(|parsed| parsed.data)
+            .unwrap_or_default();
+        if let Some(missing) = collection
+            .required_fields
+            .iter()
+            .find(|field| !fields.contains_key(*field))
+        {
+            return Err(MetadataError::MissingField(missing.clone()));
         }
-    }?;
+    }
 
-    Some(MdMetadata {
+    let post_metadata = matter
+        .parse::<PostMetadata>(content)
+        .map_err(|e| MetadataError::InvalidFrontmatter(format!("{:?}", e)))?
+        .data
+        .ok_or_else(|| MetadataError::InvalidFrontmatter("missing frontmatter".to_string()))?;
+
+    Ok(MdMetadata {
         title: post_metadata.title,
         topic: post_metadata.topic,
         description: post_metadata.description,
         tags: post_metadata.tags,
-        date: post_metadata.date,
-        is_draft: post_metadata.is_draft,
+        date: parse_post_date(&post_metadata.date)?,
+        is_draft: post_metadata
+            .is_draft
+            .unwrap_or(collection.default_visibility == Visibility::Draft),
         post_source_url: format!(
             "{}{}",
             repo_source,
             post_path.split_once(".").unwrap_or(("", post_path)).1
         ),
-        reading_time: calc_reading_time(post_path),
+        stats: ContentStats::from_markdown(content),
+        aliases: post_metadata
+            .aliases
+            .iter()
+            .map(|alias| parse_alias(alias).map(|path| path.route().to_string()))
+            .collect::<Result<Vec<String>, MetadataError>>()?,
+        revisions: Vec::new(),
+        lang: match post_metadata.lang {
+            Some(lang) => Language::from_code(&lang).ok_or(MetadataError::InvalidLanguage(lang))?,
+            None => collection.default_language,
+        },
+        translations: post_metadata
+            .translations
+            .iter()
+            .map(|translation| {
+                parse_alias(translation)
+                    .map(|path| path.as_str().to_string())
+                    .map_err(|_| MetadataError::InvalidTranslation(translation.to_string()))
+            })
+            .collect::<Result<Vec<String>, MetadataError>>()?,
+        pinned: post_metadata.pinned.unwrap_or(false),
+        weight: post_metadata.weight,
     })
 }
 
-pub fn calc_reading_time(post_path: &str) -> u8 {
-    let avg_reading_speed = 200.0; // word per minute
-    (fs::read_to_string(post_path)
-        .unwrap_or("".to_string())
-        .split_whitespace()
-        .count() as f32
-        / avg_reading_speed)
-        .ceil() as u8
+/// Aliases can be written as routes (`journal/rathmines`), full urls paths
+/// (`/posts/journal/rathmines`) or file paths (`journal/rathmines.md`).
+pub fn parse_alias(alias: &str) -> Result<PostPath, MetadataError> {
+    let route = alias.trim();
+    let route = route.strip_prefix("/posts/").unwrap_or(route);
+    let route = route.trim_start_matches('/');
+    let route = route.strip_suffix(".md").unwrap_or(route);
+
+    PostPath::from_route(route).map_err(|_| MetadataError::InvalidAlias(alias.to_string()))
+}
+
+/// Puts `pinned` first, in that order, then the posts pinned in their
+/// frontmatter by weight, then everything else sorted by `sort`.
+pub fn order_posts(posts: Vec<Post>, pinned: &[PostPath], sort: PostSort) -> Vec<Post> {
+    let pin_position = |post: &Post| {
+        pinned
+            .iter()
+            .position(|path| path.as_str() == post.file_path)
+    };
+    let by_weight = |a: &Post, b: &Post| {
+        // Posts without a weight go after the ones with it
+        (a.metadata.weight.is_none(), a.metadata.weight)
+            .cmp(&(b.metadata.weight.is_none(), b.metadata.weight))
+            .then_with(|| b.metadata.date.cmp(&a.metadata.date))
+    };
+
+    let (mut config_pinned, rest): (Vec<Post>, Vec<Post>) = posts
+        .into_iter()
+        .partition(|post| pin_position(post).is_some());
+    let (mut frontmatter_pinned, mut rest): (Vec<Post>, Vec<Post>) =
+        rest.into_iter().partition(|post| post.metadata.pinned);
+
+    config_pinned.sort_by_key(|post| pin_position(post));
+    frontmatter_pinned.sort_by(by_weight);
+    match sort {
+        PostSort::Date => rest.sort_by_key(|post| Reverse(post.metadata.date)),
+        PostSort::Title => rest.sort_by_key(|post| post.metadata.title.to_lowercase()),
+        PostSort::Manual => rest.sort_by(by_weight),
+    }
+
+    [config_pinned, frontmatter_pinned, rest].concat()
+}
+
+/// How close a post is to a path that was not found, from 0 to 1. Compares it
+/// against the route, the file name and the title, so typos and moved posts
+/// still find their match.
+pub fn suggestion_score(missing_route: &str, post: &SuggestedPost) -> f64 {
+    let missing_route = missing_route.trim_matches('/').to_lowercase();
+    let missing_name = missing_route.rsplit('/').next().unwrap_or(&missing_route);
+    let route = post.path.route().to_lowercase();
+    let name = route.rsplit('/').next().unwrap_or(&route);
+    let title = post.title.to_lowercase();
+
+    [
+        strsim::normalized_levenshtein(&missing_route, &route),
+        strsim::normalized_levenshtein(missing_name, name),
+        strsim::normalized_levenshtein(&missing_name.replace(['-', '_'], " "), &title),
+    ]
+    .into_iter()
+    .fold(0.0, f64::max)
+}
+
+pub fn parse_post_date(raw_date: &str) -> Result<DateTime<Utc>, MetadataError> {
+    let raw_date = raw_date.trim();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(raw_date) {
+        return Ok(date.with_timezone(&Utc));
+    }
+
+    ACCEPTED_DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(raw_date, format).ok())
+        .or_else(|| {
+            ACCEPTED_DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(raw_date, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .map(|date| date.and_utc())
+        .ok_or_else(|| MetadataError::InvalidDate(raw_date.to_string()))
+}
+
+/// Finds the post a wiki-style reference points to. References with a folder
+/// are relative to the garden root, bare names match the first post with
+/// that file name, the same way Obsidian resolves them.
+pub fn resolve_note_reference<'a>(
+    reference: &str,
+    file_paths: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    let reference = reference.trim().trim_start_matches('/');
+    let target = format!("{}.md", reference.strip_suffix(".md").unwrap_or(reference));
+    let file_paths: Vec<&str> = file_paths.into_iter().collect();
+
+    if file_paths.contains(&target.as_str()) {
+        return Some(target);
+    }
+    if target.contains('/') {
+        return None;
+    }
+
+    file_paths
+        .into_iter()
+        .find(|path| path.rsplit('/').next() == Some(target.as_str()))
+        .map(|path| path.to_string())
+}
+
+/// Hash identifying a version of a post, anything derived from the post can be
+/// cached under it. The path is part of it as relative links depend on it.
+pub fn content_hash(file_path: &str, content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(file_path.as_bytes());
+    hasher.update([0]);
+    hasher.update(content.as_bytes());
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// Hash of the settings every stored post was indexed with. Posts are parsed
+/// differently when any of them changes, so the whole index is stale then.
+pub fn settings_hash(repo_source: &str, collections: &Collections) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(repo_source.as_bytes());
+    for collection in collections.iter() {
+        hasher.update([0]);
+        hasher.update(
+            format!(
+                "{}\u{1f}{}\u{1f}{}\u{1f}{:?}\u{1f}{}\u{1f}{}",
+                collection.name,
+                collection.root.display(),
+                collection.prefix,
+                collection.default_visibility,
+                collection.default_language,
+                collection.required_fields.join(",")
+            )
+            .as_bytes(),
+        );
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// Hash identifying what a post renders to: its own version and the versions
+/// of the notes it embeds, so editing an embedded note changes it too.
+pub fn render_hash(post: &Post, embedded: &HashMap<String, Post>) -> String {
+    let mut embedded_hashes: Vec<String> = embedded
+        .values()
+        .map(|note| content_hash(&note.file_path, &note.content))
+        .collect();
+    embedded_hashes.sort();
+    embedded_hashes.dedup();
+
+    let mut hasher = Sha256::new();
+    hasher.update(content_hash(&post.file_path, &post.content).as_bytes());
+    for hash in embedded_hashes {
+        hasher.update([0]);
+        hasher.update(hash.as_bytes());
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone, Timelike};
+
+    #[test]
+    fn test_parse_post_date_accepted_formats() {
+        for raw in [
+            "2025-09-15",
+            "2025/09/15",
+            "15/09/2025",
+            "15-09-2025",
+            "15 Sep 2025",
+            "15 September 2025",
+            "Sep 15, 2025",
+            "September 15, 2025",
+        ] {
+            let date = parse_post_date(raw).unwrap();
+            assert_eq!((date.year(), date.month(), date.day()), (2025, 9, 15));
+            assert_eq!(date.hour(), 0);
+        }
+    }
+
+    #[test]
+    fn test_parse_post_date_with_time() {
+        let date = parse_post_date("2025-09-15T10:30:00+02:00").unwrap();
+        assert_eq!((date.day(), date.hour(), date.minute()), (15, 8, 30));
+
+        let date = parse_post_date("2025-09-15 10:30").unwrap();
+        assert_eq!((date.day(), date.hour(), date.minute()), (15, 10, 30));
+    }
+
+    #[test]
+    fn test_resolve_note_reference() {
+        let file_paths = [
+            "welcome.md",
+            "journal/2025/rathmines.md",
+            "gaming/peak/welcome.md",
+        ];
+
+        assert_eq!(
+            resolve_note_reference("journal/2025/rathmines", file_paths),
+            Some("journal/2025/rathmines.md".to_string())
+        );
+        assert_eq!(
+            resolve_note_reference("rathmines", file_paths),
+            Some("journal/2025/rathmines.md".to_string())
+        );
+        // A full match wins over a file name match deeper in the garden
+        assert_eq!(
+            resolve_note_reference("welcome", file_paths),
+            Some("welcome.md".to_string())
+        );
+        assert_eq!(resolve_note_reference("2025/rathmines", file_paths), None);
+    }
+
+    #[test]
+    fn test_parse_post_date_rejects_invalid_dates() {
+        assert!(matches!(
+            parse_post_date("yesterday"),
+            Err(MetadataError::InvalidDate(_))
+        ));
+        assert!(parse_post_date("2025-13-01").is_err());
+        assert!(parse_post_date("").is_err());
+    }
+
+    #[test]
+    fn test_post_path_accepts_garden_paths() {
+        let path = PostPath::from_route("gaming/expedition33/review").unwrap();
+        assert_eq!(path.as_str(), "gaming/expedition33/review.md");
+        assert_eq!(path.route(), "gaming/expedition33/review");
+        assert!(PostPath::from_file_path("welcome.md").is_ok());
+    }
+
+    #[test]
+    fn test_post_path_rejects_paths_outside_the_garden() {
+        for route in [
+            "",
+            "../secrets",
+            "gaming/../../etc/passwd",
+            "/etc/passwd",
+            "\\server\\share",
+            "gaming//review",
+            "./welcome",
+            "C:/Windows/win",
+            "..\\secrets",
+        ] {
+            assert!(PostPath::from_route(route).is_err(), "{}", route);
+        }
+        assert!(matches!(
+            PostPath::from_file_path("image.png"),
+            Err(PostPathError::NotMarkdown)
+        ));
+    }
+
+    #[test]
+    fn test_parse_alias_accepted_forms() {
+        for alias in [
+            "journal/rathmines",
+            "/journal/rathmines",
+            "/posts/journal/rathmines",
+            "journal/rathmines.md",
+        ] {
+            assert_eq!(parse_alias(alias).unwrap().route(), "journal/rathmines");
+        }
+        assert!(matches!(
+            parse_alias("../outside"),
+            Err(MetadataError::InvalidAlias(_))
+        ));
+    }
+
+    fn test_post(file_path: &str, title: &str) -> Post {
+        Post {
+            file_name: file_path.rsplit('/').next().unwrap().to_string(),
+            file_path: file_path.to_string(),
+            collection: String::from("garden"),
+            metadata: MdMetadata {
+                title: title.to_string(),
+                topic: None,
+                description: String::new(),
+                tags: Vec::new(),
+                date: Utc::now(),
+                is_draft: false,
+                post_source_url: String::new(),
+                stats: ContentStats::default(),
+                aliases: Vec::new(),
+                revisions: Vec::new(),
+                lang: Language::English,
+                translations: Vec::new(),
+                pinned: false,
+                weight: None,
+            },
+            content: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_suggestion_score_prefers_closest_post() {
+        let moved = SuggestedPost::new(&test_post(
+            "journal/2025/rathmines.md",
+            "Living in Rathmines",
+        ))
+        .unwrap();
+        let unrelated =
+            SuggestedPost::new(&test_post("gaming/peak/review.md", "Peak review")).unwrap();
+
+        assert!(suggestion_score("rathmine", &moved) > 0.8);
+        assert!(
+            suggestion_score("journal/rathmines", &moved)
+                > suggestion_score("journal/rathmines", &unrelated)
+        );
+        assert!(suggestion_score("/living-in-rathmines", &moved) > 0.9);
+    }
+
+    #[test]
+    fn test_render_hash_changes_with_embedded_notes() {
+        let post = test_post("welcome.md", "Welcome");
+        let mut note = test_post("notes/mesa.md", "Mesa");
+        let mut embedded = HashMap::new();
+        embedded.insert(String::from("notes/mesa"), note.clone());
+        let before = render_hash(&post, &embedded);
+
+        // The same note under another reference renders the same
+        embedded.insert(String::from("mesa"), note.clone());
+        assert_eq!(render_hash(&post, &embedded), before);
+
+        note.content = String::from("Edited");
+        embedded.insert(String::from("notes/mesa"), note.clone());
+        embedded.insert(String::from("mesa"), note);
+        assert_ne!(render_hash(&post, &embedded), before);
+        assert_ne!(render_hash(&post, &HashMap::new()), before);
+    }
+
+    #[test]
+    fn test_order_posts_puts_pins_first() {
+        let dated = |file_path: &str, title: &str, day: u32| Post {
+            metadata: MdMetadata {
+                date: Utc.with_ymd_and_hms(2025, 10, day, 0, 0, 0).unwrap(),
+                ..test_post(file_path, title).metadata
+            },
+            ..test_post(file_path, title)
+        };
+        let mut weighted = dated("peak.md", "Peak", 3);
+        weighted.metadata.pinned = true;
+        weighted.metadata.weight = Some(2);
+        let mut light = dated("katamari.md", "Katamari", 1);
+        light.metadata.weight = Some(1);
+        let posts = vec![
+            dated("welcome.md", "Welcome", 1),
+            dated("hello.md", "Hello", 2),
+            weighted,
+            light,
+            dated("rathmines.md", "Rathmines", 4),
+        ];
+        let pinned = [
+            PostPath::from_file_path("welcome.md").unwrap(),
+            PostPath::from_file_path("missing.md").unwrap(),
+        ];
+        let order = |sort: PostSort| -> Vec<String> {
+            order_posts(posts.clone(), &pinned, sort)
+                .into_iter()
+                .map(|post| post.file_path)
+                .collect()
+        };
+
+        assert_eq!(
+            order(PostSort::Date),
+            [
+                "welcome.md",
+                "peak.md",
+                "rathmines.md",
+                "hello.md",
+                "katamari.md"
+            ]
+        );
+        assert_eq!(
+            order(PostSort::Title),
+            [
+                "welcome.md",
+                "peak.md",
+                "hello.md",
+                "katamari.md",
+                "rathmines.md"
+            ]
+        );
+        assert_eq!(
+            order(PostSort::Manual),
+            [
+                "welcome.md",
+                "peak.md",
+                "katamari.md",
+                "rathmines.md",
+                "hello.md"
+            ]
+        );
+    }
 }
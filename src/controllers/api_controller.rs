@@ -0,0 +1,36 @@
+use actix_web::{HttpResponse, get, web};
+use search_engine::stats::ContentStats;
+use serde::Serialize;
+
+use crate::controllers::{AppState, parse_post_route};
+
+pub fn configure_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(post_stats);
+}
+
+#[derive(Serialize)]
+struct PostStats<'a> {
+    file_path: &'a str,
+    title: &'a str,
+    /// In minutes, at the configured reading speeds.
+    reading_time: u32,
+    #[serde(flatten)]
+    stats: &'a ContentStats,
+}
+
+#[get("/api/posts/{post:.*}/stats")]
+async fn post_stats(app_state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    let Some(post_path) = parse_post_route(&path) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let Some(post) = app_state.post_search_engine.get_post(&post_path).await else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    HttpResponse::Ok().json(PostStats {
+        file_path: &post.file_path,
+        title: &post.metadata.title,
+        reading_time: post.metadata.stats.reading_time(&app_state.reading_speed),
+        stats: &post.metadata.stats,
+    })
+}
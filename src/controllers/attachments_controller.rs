@@ -0,0 +1,72 @@
+use actix_files::NamedFile;
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use serde::Deserialize;
+
+use crate::controllers::{AppState, blocking_result};
+use crate::helpers::{
+    garden::disk_path,
+    images::{garden_relative_path, get_or_render_variant, is_resizable},
+};
+
+pub fn configure_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(post_attachment);
+}
+
+#[derive(Deserialize)]
+struct VariantParams {
+    w: Option<u32>,
+    format: Option<String>,
+}
+
+// Registered before the posts controller, which would otherwise take these
+// paths as posts
+#[get("/posts/{attachment:.+\\.(?:png|jpg|jpeg|gif|webp|svg|avif)}")]
+async fn post_attachment(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    params: web::Query<VariantParams>,
+) -> HttpResponse {
+    let Some(attachment) = garden_relative_path(path.as_str()) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let attachment = attachment.to_string_lossy();
+    let Some(file_path) = disk_path(&attachment) else {
+        return HttpResponse::NotFound().finish();
+    };
+    // Drafts are hidden, and so is what only they use
+    if !file_path.is_file()
+        || app_state
+            .post_search_engine
+            .is_draft_attachment(&attachment)
+            .await
+    {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let webp = params.format.as_deref() == Some("webp");
+    if (params.w.is_none() && !webp) || !is_resizable(&file_path) {
+        return match NamedFile::open_async(&file_path).await {
+            Ok(file) => file.into_response(&req),
+            Err(_) => HttpResponse::NotFound().finish(),
+        };
+    }
+
+    let width = params.w;
+    let variant = web::block({
+        let file_path = file_path.clone();
+        move || get_or_render_variant(&file_path, width, webp)
+    })
+    .await;
+
+    let Some((bytes, content_type)) =
+        blocking_result(variant, &format!("render image {}", file_path.display()))
+    else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Cache-Control", "public, max-age=86400"))
+        .body(bytes)
+}
@@ -11,7 +11,8 @@ use maud::{PreEscaped, html};
 use serde_json::json;
 use std::{collections::HashMap, time::Duration};
 
-use crate::controllers::{AppState, wrap_content_into_full_page};
+use crate::controllers::{AppState, PageMeta, wrap_content_into_full_page};
+use crate::helpers::{markdown::render_markdown, sanitize::SanitizeProfile};
 
 pub fn configure_services(cfg: &mut web::ServiceConfig) {
     cfg.service(chess_stats_by_game)
@@ -51,7 +52,7 @@ async fn chess_stats_by_game(
             let fallback_html =
                 std::fs::read_to_string(format!("{}/chess_stats_fallback.md", template_path))
                     .unwrap_or_else(|_| "Error loading chess stats".to_string());
-            return Html::new(markdown::to_html(&fallback_html));
+            return Html::new(render_markdown(&fallback_html, SanitizeProfile::Untrusted));
         }
     };
 
@@ -59,8 +60,14 @@ async fn chess_stats_by_game(
     if is_htmx_req {
         Html::new(render_chess_page(game_mode, rating_history, player_stats))
     } else {
+        let meta = PageMeta::new(
+            "Chess stats",
+            &format!("My {} chess ratings and stats on Lichess", game_mode),
+            &format!("/chess/stats/{}", game_mode),
+        );
         Html::new(wrap_content_into_full_page(
-            &app_state.app_name,
+            &app_state,
+            &meta,
             render_chess_page(game_mode, rating_history, player_stats)
                 .0
                 .as_str(),
@@ -91,7 +98,7 @@ async fn chess_games_analysis(app_state: web::Data<AppState>) -> Html {
             let fallback_html =
                 std::fs::read_to_string(format!("{}/chess_stats_fallback.md", template_path))
                     .unwrap_or_else(|_| "Error loading chess stats".to_string());
-            return Html::new(markdown::to_html(&fallback_html));
+            return Html::new(render_markdown(&fallback_html, SanitizeProfile::Untrusted));
         }
     };
 
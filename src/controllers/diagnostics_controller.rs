@@ -0,0 +1,79 @@
+use actix_web::{
+    HttpRequest, get,
+    web::{self, Html},
+};
+use maud::html;
+
+use crate::controllers::{AppState, PageMeta, wrap_content_into_full_page};
+
+pub fn configure_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(aliases);
+}
+
+#[get("/diagnostics/aliases")]
+async fn aliases(app_state: web::Data<AppState>, req: HttpRequest) -> Html {
+    let redirects = app_state.post_search_engine.redirects();
+
+    let content = html! {
+        div
+        class="p-4 md:p-6 lg:p-8 overflow-auto w-full"
+        {
+            div
+            class="max-w-5xl mx-auto"
+            {
+                h1 class="text-4xl font-bold mb-2" { "Aliases" }
+                p class="text-zinc-400 mb-8" {
+                    "Old post paths and where they redirect to, from the "
+                    code { "aliases" }
+                    " frontmatter and from renames found in git history."
+                }
+                @if redirects.is_empty() {
+                    p class="text-zinc-500 text-sm" { "No post has been moved yet." }
+                } @else {
+                    table class="w-full text-sm border border-shade-color" {
+                        thead {
+                            tr class="text-left text-zinc-500 text-xs uppercase tracking-widest" {
+                                th class="px-4 py-2 border-b border-shade-color" { "Old path" }
+                                th class="px-4 py-2 border-b border-shade-color" { "Redirects to" }
+                                th class="px-4 py-2 border-b border-shade-color" { "Source" }
+                            }
+                        }
+                        tbody {
+                            @for (old_path, redirect) in &redirects {
+                                tr class="hover:bg-shade-color transition-colors" {
+                                    td class="px-4 py-2 border-b border-shade-color" {
+                                        code { "/posts/" (old_path.route()) }
+                                    }
+                                    td class="px-4 py-2 border-b border-shade-color" {
+                                        a
+                                        href=(format!("/posts/{}", redirect.target.route()))
+                                        class="hover:text-primary-color"
+                                        {
+                                            "/posts/" (redirect.target.route())
+                                        }
+                                    }
+                                    td class="px-4 py-2 border-b border-shade-color text-zinc-500" {
+                                        (redirect.source.label())
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    .into_string();
+
+    let is_htmx_req = req.headers().get("HX-Request").is_some();
+    if is_htmx_req {
+        Html::new(content)
+    } else {
+        let meta = PageMeta::new(
+            "Aliases",
+            "Old post paths and where they redirect to",
+            "/diagnostics/aliases",
+        );
+        Html::new(wrap_content_into_full_page(&app_state, &meta, &content))
+    }
+}
@@ -1,6 +1,81 @@
-use actix_web::{Responder, get};
+use actix_web::{
+    HttpRequest, HttpResponse, get,
+    http::header::ContentType,
+    web::{self, Data},
+};
+use maud::html;
+
+use crate::controllers::{AppState, PageMeta, wrap_content_into_full_page};
+
+const MAX_SUGGESTIONS: usize = 5;
 
 #[get("/{url:.*}")]
-pub async fn fallback_route() -> impl Responder {
-    String::from("Fallback page")
+pub async fn fallback_route(app_state: web::Data<AppState>, req: HttpRequest) -> HttpResponse {
+    not_found_page(&app_state, &req, req.path()).await
+}
+
+/// 404 page for anything that is not in the garden, with the posts whose
+/// path or title look the most like `missing_route`.
+pub async fn not_found_page(
+    app_state: &Data<AppState>,
+    req: &HttpRequest,
+    missing_route: &str,
+) -> HttpResponse {
+    let suggestions = app_state
+        .post_search_engine
+        .suggest_posts(missing_route, MAX_SUGGESTIONS);
+
+    let content = html! {
+        div
+        class="p-4 md:p-6 lg:p-8 overflow-auto w-full"
+        {
+            div
+            class="max-w-3xl mx-auto"
+            {
+                p class="text-primary-color text-sm font-semibold uppercase tracking-wider mb-2" { "404" }
+                h1 class="text-4xl md:text-5xl font-bold mb-4" { "This page is not in the garden" }
+                p class="text-zinc-400 mb-8" {
+                    "Nothing lives at "
+                    code { (req.path()) }
+                    ", it may have been moved or never existed."
+                }
+                @if !suggestions.is_empty() {
+                    div class="flex items-center gap-3 w-full mb-3" {
+                        p class="text-primary-color text-sm font-semibold uppercase tracking-wider" { "Maybe you were looking for" }
+                        div class="flex-1 h-px bg-shade-color" {}
+                    }
+                    ul class="flex flex-col gap-px border-t border-shade-color mb-8" {
+                        @for post in &suggestions {
+                            li {
+                                a
+                                href=(format!("/posts/{}", post.path.route()))
+                                class="flex flex-col gap-1 px-4 py-3 border-b border-r border-shade-color border-l hover:border-l-2 border-l-shade-color hover:border-l-primary-color hover:bg-shade-color transition-colors"
+                                {
+                                    span class="text-zinc-100 text-sm font-semibold" { (post.title) }
+                                    span class="text-zinc-500 text-xs" { (post.description) }
+                                }
+                            }
+                        }
+                    }
+                }
+                div class="flex gap-4 text-sm" {
+                    a href="/" class="hover:text-primary-color" { "← Back home" }
+                    a href="/garden-view-dispatcher" class="hover:text-primary-color" { "Browse the garden" }
+                }
+            }
+        }
+    }
+    .into_string();
+
+    let is_htmx_req = req.headers().get("HX-Request").is_some();
+    let body = if is_htmx_req {
+        content
+    } else {
+        let meta = PageMeta::new("Not found", "This page is not in the garden", req.path());
+        wrap_content_into_full_page(app_state, &meta, &content)
+    };
+
+    HttpResponse::NotFound()
+        .content_type(ContentType::html())
+        .body(body)
 }
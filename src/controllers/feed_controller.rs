@@ -0,0 +1,333 @@
+use actix_web::{HttpResponse, get, web};
+use atom_syndication::{
+    CategoryBuilder as AtomCategoryBuilder, ContentBuilder, EntryBuilder, FeedBuilder, LinkBuilder,
+    PersonBuilder,
+};
+use rss::{CategoryBuilder, ChannelBuilder, GuidBuilder, ItemBuilder};
+use search_engine::{
+    language::Language,
+    types::{MAX_NEWS_PAGE_SIZE, NewsParams},
+    utils::Post,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::controllers::{AppState, SITE_AUTHOR, SITE_DESCRIPTION};
+use crate::helpers::markdown::render_indexed_post;
+
+pub fn configure_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(rss_feed).service(atom_feed).service(json_feed);
+}
+
+#[derive(Deserialize, Serialize)]
+struct FeedParams {
+    tag: Option<String>,
+    topic: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    description: String,
+    language: String,
+    authors: Vec<JsonFeedAuthor>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor {
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    summary: String,
+    content_html: String,
+    date_published: String,
+    tags: Vec<String>,
+}
+
+// Everything a feed needs to know about the posts it lists, shared by the
+// three formats so they always describe the same selection.
+struct FeedContext {
+    title: String,
+    description: String,
+    home_page_url: String,
+    feed_url: String,
+    /// The language most of the posts are written in.
+    language: Language,
+    posts: Vec<Post>,
+    // Rendered content of the posts by file path, rendering needs the index to
+    // find the notes they embed
+    html: HashMap<String, String>,
+}
+
+impl FeedContext {
+    async fn new(app_state: &AppState, params: &FeedParams, feed_path: &str) -> FeedContext {
+        let news_params = NewsParams {
+            tag: params.tag.clone(),
+            topic: params.topic.clone(),
+            page_size: Some(MAX_NEWS_PAGE_SIZE),
+            ..Default::default()
+        };
+        let posts = app_state
+            .post_search_engine
+            .query_news(&news_params)
+            .await
+            .posts;
+        let mut html = HashMap::new();
+        for post in &posts {
+            let rendered = render_indexed_post(&app_state.post_search_engine, post).await;
+            html.insert(post.file_path.clone(), rendered.html.clone());
+        }
+
+        let filters_query = serde_urlencoded::to_string(params)
+            .ok()
+            .filter(|q| !q.is_empty())
+            .map(|q| format!("?{}", q))
+            .unwrap_or_default();
+
+        let title = match (&params.tag, &params.topic) {
+            (Some(tag), _) => format!("{} · #{}", app_state.app_name, tag),
+            (None, Some(topic)) => format!("{} · {}", app_state.app_name, topic),
+            (None, None) => app_state.app_name.clone(),
+        };
+
+        FeedContext {
+            title,
+            description: String::from(SITE_DESCRIPTION),
+            home_page_url: format!("{}/news{}", app_state.site_url, filters_query),
+            feed_url: format!("{}{}{}", app_state.site_url, feed_path, filters_query),
+            language: dominant_language(&posts),
+            posts,
+            html,
+        }
+    }
+
+    fn post_html(&self, post: &Post) -> String {
+        self.html.get(&post.file_path).cloned().unwrap_or_default()
+    }
+
+    fn post_url(&self, site_url: &str, post: &Post) -> String {
+        format!(
+            "{}/posts/{}",
+            site_url,
+            post.file_path
+                .strip_suffix(".md")
+                .unwrap_or(&post.file_path)
+        )
+    }
+}
+
+// Ties go to the language listed first, an empty feed is in the default one
+fn dominant_language(posts: &[Post]) -> Language {
+    Language::ALL
+        .into_iter()
+        .rev()
+        .max_by_key(|language| {
+            posts
+                .iter()
+                .filter(|post| post.metadata.lang == *language)
+                .count()
+        })
+        .unwrap_or_default()
+}
+
+#[get("/feed.xml")]
+async fn rss_feed(app_state: web::Data<AppState>, params: web::Query<FeedParams>) -> HttpResponse {
+    let ctx = FeedContext::new(&app_state, &params, "/feed.xml").await;
+
+    let items = ctx
+        .posts
+        .iter()
+        .map(|post| {
+            let url = ctx.post_url(&app_state.site_url, post);
+            ItemBuilder::default()
+                .title(Some(post.metadata.title.clone()))
+                .link(Some(url.clone()))
+                .guid(Some(
+                    GuidBuilder::default().value(url).permalink(true).build(),
+                ))
+                .description(Some(post.metadata.description.clone()))
+                .content(Some(ctx.post_html(post)))
+                .pub_date(Some(post.metadata.date.to_rfc2822()))
+                .categories(
+                    post.metadata
+                        .tags
+                        .iter()
+                        .map(|tag| CategoryBuilder::default().name(tag.clone()).build())
+                        .collect::<Vec<_>>(),
+                )
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    let channel = ChannelBuilder::default()
+        .title(ctx.title.clone())
+        .link(ctx.home_page_url.clone())
+        .description(ctx.description.clone())
+        .language(Some(ctx.language.code().to_string()))
+        .last_build_date(ctx.posts.first().map(|p| p.metadata.date.to_rfc2822()))
+        .items(items)
+        .build();
+
+    HttpResponse::Ok()
+        .content_type("application/rss+xml; charset=utf-8")
+        .body(channel.to_string())
+}
+
+#[get("/atom.xml")]
+async fn atom_feed(app_state: web::Data<AppState>, params: web::Query<FeedParams>) -> HttpResponse {
+    let ctx = FeedContext::new(&app_state, &params, "/atom.xml").await;
+
+    let entries = ctx
+        .posts
+        .iter()
+        .map(|post| {
+            let url = ctx.post_url(&app_state.site_url, post);
+            EntryBuilder::default()
+                .id(url.clone())
+                .title(post.metadata.title.clone())
+                .updated(post.metadata.date.fixed_offset())
+                .published(Some(post.metadata.date.fixed_offset()))
+                .summary(Some(post.metadata.description.clone().into()))
+                .content(Some(
+                    ContentBuilder::default()
+                        .content_type(Some(String::from("html")))
+                        .value(Some(ctx.post_html(post)))
+                        .build(),
+                ))
+                .links(vec![
+                    LinkBuilder::default()
+                        .href(url)
+                        .rel(String::from("alternate"))
+                        .build(),
+                ])
+                .categories(
+                    post.metadata
+                        .tags
+                        .iter()
+                        .map(|tag| AtomCategoryBuilder::default().term(tag.clone()).build())
+                        .collect::<Vec<_>>(),
+                )
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    let feed = FeedBuilder::default()
+        .id(ctx.feed_url.clone())
+        .title(ctx.title.clone())
+        .subtitle(Some(ctx.description.clone().into()))
+        .lang(Some(ctx.language.code().to_string()))
+        .updated(
+            ctx.posts
+                .first()
+                .map(|p| p.metadata.date)
+                .unwrap_or_default()
+                .fixed_offset(),
+        )
+        .authors(vec![PersonBuilder::default().name(SITE_AUTHOR).build()])
+        .links(vec![
+            LinkBuilder::default()
+                .href(ctx.feed_url.clone())
+                .rel(String::from("self"))
+                .build(),
+            LinkBuilder::default()
+                .href(ctx.home_page_url.clone())
+                .rel(String::from("alternate"))
+                .build(),
+        ])
+        .entries(entries)
+        .build();
+
+    HttpResponse::Ok()
+        .content_type("application/atom+xml; charset=utf-8")
+        .body(feed.to_string())
+}
+
+#[get("/feed.json")]
+async fn json_feed(app_state: web::Data<AppState>, params: web::Query<FeedParams>) -> HttpResponse {
+    let ctx = FeedContext::new(&app_state, &params, "/feed.json").await;
+
+    let items = ctx
+        .posts
+        .iter()
+        .map(|post| {
+            let url = ctx.post_url(&app_state.site_url, post);
+            JsonFeedItem {
+                id: url.clone(),
+                url,
+                title: post.metadata.title.clone(),
+                summary: post.metadata.description.clone(),
+                content_html: ctx.post_html(post),
+                date_published: post.metadata.date.to_rfc3339(),
+                tags: post.metadata.tags.clone(),
+            }
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: ctx.title,
+        home_page_url: ctx.home_page_url,
+        feed_url: ctx.feed_url,
+        description: ctx.description,
+        language: ctx.language.code().to_string(),
+        authors: vec![JsonFeedAuthor { name: SITE_AUTHOR }],
+        items,
+    };
+
+    HttpResponse::Ok()
+        .content_type("application/feed+json; charset=utf-8")
+        .json(feed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use search_engine::utils::MdMetadata;
+
+    fn post(lang: Language) -> Post {
+        Post {
+            file_name: String::from("post.md"),
+            file_path: String::from("post.md"),
+            collection: String::from("garden"),
+            metadata: MdMetadata {
+                title: String::from("Post"),
+                topic: None,
+                description: String::new(),
+                tags: Vec::new(),
+                date: chrono::Utc::now(),
+                is_draft: false,
+                post_source_url: String::new(),
+                stats: Default::default(),
+                aliases: Vec::new(),
+                revisions: Vec::new(),
+                lang,
+                translations: Vec::new(),
+                pinned: false,
+                weight: None,
+            },
+            content: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_feed_language_follows_most_posts() {
+        let posts = [
+            post(Language::Spanish),
+            post(Language::English),
+            post(Language::Spanish),
+        ];
+
+        assert_eq!(dominant_language(&posts), Language::Spanish);
+        assert_eq!(dominant_language(&posts[..2]), Language::English);
+        assert_eq!(dominant_language(&[]), Language::English);
+    }
+}
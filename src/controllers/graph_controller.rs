@@ -1,10 +1,12 @@
 use actix_web::{
-    HttpRequest, get,
+    HttpRequest, HttpResponse, get,
+    http::header::ContentType,
     web::{self, Html},
 };
 use maud::html;
+use search_engine::types::GraphParams;
 
-use crate::controllers::{AppState, wrap_content_into_full_page};
+use crate::controllers::{AppState, PageMeta, parse_post_route, wrap_content_into_full_page};
 
 pub fn configure_services(cfg: &mut web::ServiceConfig) {
     cfg.service(graph_network)
@@ -13,14 +15,14 @@ pub fn configure_services(cfg: &mut web::ServiceConfig) {
 }
 
 #[get("/graph/{current_url_pathname:.*}")]
-async fn graph_network(app_state: web::Data<AppState>, path: web::Path<String>) -> Html {
-    let graph_data = {
-        let file_path = format!("{}.md", path.as_str());
-        app_state
-            .post_search_engine
-            .get_graph_from_related_posts(&file_path)
-            .await
+async fn graph_network(app_state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    let Some(post_path) = parse_post_route(&path) else {
+        return HttpResponse::NotFound().finish();
     };
+    let graph_data = app_state
+        .post_search_engine
+        .get_graph_from_related_posts(&post_path)
+        .await;
 
     let nodes_json = serde_json::to_string(&graph_data.nodes).unwrap();
     let edges_json = serde_json::to_string(&graph_data.edges).unwrap();
@@ -32,19 +34,41 @@ async fn graph_network(app_state: web::Data<AppState>, path: web::Path<String>)
             data-edges=(edges_json) {}
     };
 
-    Html::new(graph)
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(graph.into_string())
 }
 
 #[get("/garden-view-dispatcher")]
 async fn garden_view_dispatcher(
     app_state: web::Data<AppState>,
     req: HttpRequest,
+    params: web::Query<GraphParams>,
 ) -> impl actix_web::Responder {
+    let collection = params.collection.as_deref().filter(|c| !c.is_empty());
+    let collections = app_state.post_search_engine.collections().names();
+
     let h = html! {
+        @if collections.len() > 1 {
+            nav
+            class="flex flex-wrap gap-2 p-2"
+            {
+                a
+                href="/garden-view-dispatcher"
+                class=(collection_link_class(collection.is_none()))
+                { "All" }
+                @for name in &collections {
+                    a
+                    href=(garden_view_url("/garden-view-dispatcher", Some(name)))
+                    class=(collection_link_class(collection == Some(name.as_str())))
+                    { (name) }
+                }
+            }
+        }
         div
         #garden-view-section
         style="width: 100%; height: 100%;"
-        hx-get="/garden-view"
+        hx-get=(garden_view_url("/garden-view", collection))
         hx-target="#garden-view-section"
         hx-trigger="load"
         hx-swap="innerHTML"
@@ -56,13 +80,21 @@ async fn garden_view_dispatcher(
     if is_htmx_req {
         Html::new(h)
     } else {
-        Html::new(wrap_content_into_full_page(&app_state.app_name, &h))
+        let meta = PageMeta::new(
+            "Garden",
+            "A graph of every note in the garden and how they link to each other",
+            "/garden-view-dispatcher",
+        );
+        Html::new(wrap_content_into_full_page(&app_state, &meta, &h))
     }
 }
 
 #[get("/garden-view")]
-async fn garden_view(app_state: web::Data<AppState>) -> Html {
-    let graph_data = app_state.post_search_engine.get_overall_graph_data().await;
+async fn garden_view(app_state: web::Data<AppState>, params: web::Query<GraphParams>) -> Html {
+    let graph_data = app_state
+        .post_search_engine
+        .get_overall_graph_data(&params)
+        .await;
     let nodes_json = serde_json::to_string(&graph_data.nodes).unwrap();
     let edges_json = serde_json::to_string(&graph_data.edges).unwrap();
 
@@ -75,3 +107,21 @@ async fn garden_view(app_state: web::Data<AppState>) -> Html {
 
     Html::new(graph)
 }
+
+fn garden_view_url(path: &str, collection: Option<&str>) -> String {
+    let params = GraphParams {
+        collection: collection.map(String::from),
+    };
+    match serde_urlencoded::to_string(&params) {
+        Ok(query) if !query.is_empty() => format!("{}?{}", path, query),
+        _ => path.to_string(),
+    }
+}
+
+fn collection_link_class(is_active: bool) -> &'static str {
+    if is_active {
+        "px-3 py-1 text-xs font-medium rounded-full bg-primary-color text-background-color"
+    } else {
+        "px-3 py-1 text-xs font-medium rounded-full bg-primary-color/10 text-primary-color hover:bg-primary-color/20 transition-colors"
+    }
+}
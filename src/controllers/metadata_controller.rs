@@ -1,19 +1,29 @@
-use crate::controllers::AppState;
-use actix_web::{
-    get,
-    web::{self, Html},
-};
+use crate::controllers::{AppState, parse_post_route};
+use crate::helpers::{format_date, format_relative_date};
+use actix_web::{HttpResponse, get, http::header::ContentType, web};
 use maud::html;
 pub fn configure_services(cfg: &mut web::ServiceConfig) {
     cfg.service(render_metadata);
 }
 #[get("/metadata/{post:.*}")]
-async fn render_metadata(app_state: web::Data<AppState>, path: web::Path<String>) -> Html {
-    let file_path = format!("{}.md", path.as_str());
-    let post = app_state.post_search_engine.get_post(&file_path).await;
-    match post {
+async fn render_metadata(app_state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    let Some(post_path) = parse_post_route(&path) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let post = app_state.post_search_engine.get_post(&post_path).await;
+    let html = match post {
         Some(p) => {
-            let html = html! {
+            // Indent relative to the shallowest heading, posts don't always start at h1
+            let outline_depth = p
+                .metadata
+                .stats
+                .headings
+                .iter()
+                .map(|heading| heading.depth)
+                .min()
+                .unwrap_or(1);
+
+            html! {
                 div class="flex flex-col gap-2 w-full" {
                     div class="flex items-center gap-3 w-full" {
                         p class="text-primary-color text-sm font-semibold uppercase tracking-wider" { "Metadata" }
@@ -30,11 +40,51 @@ async fn render_metadata(app_state: web::Data<AppState>, path: web::Path<String>
                         }
                         div class="flex flex-col gap-1 px-4 py-3 border-b border-r border-shade-color border-l hover:border-l-2 border-l-shade-color hover:border-l-primary-color hover:bg-shade-color transition-colors" {
                             p class="text-zinc-500 text-xs uppercase tracking-widest" { "Reading time" }
-                            p class="text-sm font-semibold" { (p.metadata.reading_time) " min" }
+                            p class="text-sm font-semibold" { (p.metadata.stats.reading_time(&app_state.reading_speed)) " min" }
+                        }
+                        div class="flex flex-col gap-1 px-4 py-3 border-b border-r border-shade-color border-l hover:border-l-2 border-l-shade-color hover:border-l-primary-color hover:bg-shade-color transition-colors" {
+                            p class="text-zinc-500 text-xs uppercase tracking-widest" { "Content" }
+                            p class="text-sm font-semibold" { (p.metadata.stats.words) " words" }
+                            p class="text-zinc-500 text-xs" {
+                                (p.metadata.stats.code_blocks) " code blocks · "
+                                (p.metadata.stats.images) " images · "
+                                (p.metadata.stats.links) " links"
+                            }
+                        }
+                        @if !p.metadata.stats.headings.is_empty() {
+                            div class="flex flex-col gap-1 px-4 py-3 border-b border-r border-shade-color border-l hover:border-l-2 border-l-shade-color hover:border-l-primary-color hover:bg-shade-color transition-colors" {
+                                p class="text-zinc-500 text-xs uppercase tracking-widest" { "Outline" }
+                                ul class="flex flex-col gap-1 text-xs text-zinc-300" {
+                                    @for heading in &p.metadata.stats.headings {
+                                        li style=(format!("padding-left: {}rem", heading.depth.saturating_sub(outline_depth) as f32 * 0.75)) {
+                                            (heading.text)
+                                        }
+                                    }
+                                }
+                            }
                         }
                         div class="flex flex-col gap-1 px-4 py-3 border-b border-r border-shade-color border-l hover:border-l-2 border-l-shade-color hover:border-l-primary-color hover:bg-shade-color transition-colors" {
                             p class="text-zinc-500 text-xs uppercase tracking-widest" { "Date" }
-                            p class="text-sm font-semibold" { (p.metadata.date) }
+                            p class="text-sm font-semibold" {
+                                time datetime=(p.metadata.date.to_rfc3339()) { (format_date(&p.metadata.date)) }
+                            }
+                            p class="text-zinc-500 text-xs" { (format_relative_date(&p.metadata.date)) }
+                        }
+                        @if let Some(updated_at) = p.metadata.updated_at() {
+                            div class="flex flex-col gap-1 px-4 py-3 border-b border-r border-shade-color border-l hover:border-l-2 border-l-shade-color hover:border-l-primary-color hover:bg-shade-color transition-colors" {
+                                p class="text-zinc-500 text-xs uppercase tracking-widest" { "Last updated" }
+                                p class="text-sm font-semibold" {
+                                    time datetime=(updated_at.to_rfc3339()) { "Updated " (format_relative_date(&updated_at)) }
+                                }
+                                a
+                                href=(format!("/posts/{}/history", post_path.route()))
+                                hx-target="#main-section"
+                                hx-swap="innerHTML transition:true"
+                                class="text-zinc-500 hover:text-primary-color text-xs"
+                                {
+                                    (p.metadata.revisions.len()) " revisions →"
+                                }
+                            }
                         }
                         div class="flex flex-col gap-1 px-4 py-3 border-b border-r border-shade-color border-l hover:border-l-2 border-l-shade-color hover:border-l-primary-color hover:bg-shade-color transition-colors" {
                             p class="text-zinc-500 text-xs uppercase tracking-widest" { "Tags" }
@@ -51,11 +101,10 @@ async fn render_metadata(app_state: web::Data<AppState>, path: web::Path<String>
                         }
                     }
                 }
-            };
-            Html::new(html)
+            }
         }
         None => {
-            let html = html! {
+            html! {
                 div class="flex flex-col gap-3 p-6 border border-shade-color" {
                     div class="flex items-center gap-3" {
                         p class="text-primary-color text-sm font-semibold uppercase tracking-wider" { "Metadata" }
@@ -63,8 +112,11 @@ async fn render_metadata(app_state: web::Data<AppState>, path: web::Path<String>
                     }
                     p class="text-zinc-500 text-sm" { "No metadata found for this post." }
                 }
-            };
-            Html::new(html)
+            }
         }
-    }
+    };
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html.into_string())
 }
@@ -1,37 +1,215 @@
+use actix_web::error::BlockingError;
 use chess_module::LichessState;
 use games_module::SteamState;
+use maud::{Markup, PreEscaped, html};
 use music_module::SpotifyState;
-use search_engine::PostsSearchEngine;
+use search_engine::{
+    PostsSearchEngine,
+    stats::ReadingSpeed,
+    utils::{Post, PostPath},
+};
+use serde_json::json;
+use std::fmt::Display;
 use std::sync::{Arc, OnceLock};
 
+pub mod api_controller;
+pub mod attachments_controller;
 pub mod chess_controller;
+pub mod diagnostics_controller;
 pub mod fallback_controller;
+pub mod feed_controller;
 pub mod graph_controller;
 pub mod metadata_controller;
 pub mod music_controller;
 pub mod news_controller;
+pub mod og_controller;
 pub mod posts_controller;
+pub mod preview_controller;
 pub mod routines_controller;
+pub mod seo_controller;
 pub mod steam_controller;
 
 static INDEX_TEMPLATE: OnceLock<String> = OnceLock::new();
 
+pub const SITE_AUTHOR: &str = "Gus";
+pub const SITE_DESCRIPTION: &str = "My personal digital garden, built with Rust + HTMX";
+
 pub struct AppState {
     pub app_name: String,
+    pub site_url: String,
     pub lichess_state: LichessState,
     pub spotify_state: Arc<tokio::sync::Mutex<SpotifyState>>,
     pub steam_state: SteamState,
     pub post_search_engine: Arc<PostsSearchEngine>,
+    pub reading_speed: ReadingSpeed,
+}
+
+/// Everything that goes into the `<head>` of a full page render.
+pub struct PageMeta {
+    pub title: Option<String>,
+    pub description: String,
+    pub path: String,
+    pub post: Option<Post>,
+}
+
+impl PageMeta {
+    pub fn new(title: &str, description: &str, path: &str) -> PageMeta {
+        PageMeta {
+            title: Some(title.to_string()),
+            description: description.to_string(),
+            path: path.to_string(),
+            post: None,
+        }
+    }
+
+    pub fn for_post(post: Post, path: &str) -> PageMeta {
+        PageMeta {
+            title: Some(post.metadata.title.clone()),
+            description: post.metadata.description.clone(),
+            path: path.to_string(),
+            post: Some(post),
+        }
+    }
+
+    pub fn site_default(path: &str) -> PageMeta {
+        PageMeta {
+            title: None,
+            description: SITE_DESCRIPTION.to_string(),
+            path: path.to_string(),
+            post: None,
+        }
+    }
+
+    fn full_title(&self, app_name: &str) -> String {
+        match &self.title {
+            Some(title) => format!("{} · {}", title, app_name),
+            None => app_name.to_string(),
+        }
+    }
+
+    fn render_head(&self, app_state: &AppState) -> Markup {
+        let title = self.full_title(&app_state.app_name);
+        let canonical_url = format!("{}{}", app_state.site_url, self.path);
+        let og_type = if self.post.is_some() {
+            "article"
+        } else {
+            "website"
+        };
+        let og_image = self.post.as_ref().map(|post| {
+            format!(
+                "{}/og/{}.png",
+                app_state.site_url,
+                post.file_path
+                    .strip_suffix(".md")
+                    .unwrap_or(&post.file_path)
+            )
+        });
+
+        html! {
+            title { (title) }
+            meta name="description" content=(self.description);
+            link rel="canonical" href=(canonical_url);
+
+            meta property="og:site_name" content=(app_state.app_name);
+            meta property="og:title" content=(self.title.as_deref().unwrap_or(&app_state.app_name));
+            meta property="og:description" content=(self.description);
+            meta property="og:type" content=(og_type);
+            meta property="og:url" content=(canonical_url);
+
+            meta name="twitter:title" content=(title);
+            meta name="twitter:description" content=(self.description);
+            @if let Some(og_image) = &og_image {
+                meta property="og:image" content=(og_image);
+                meta property="og:image:width" content="1200";
+                meta property="og:image:height" content="630";
+                meta name="twitter:card" content="summary_large_image";
+                meta name="twitter:image" content=(og_image);
+            } @else {
+                meta name="twitter:card" content="summary";
+            }
+
+            @if let Some(post) = &self.post {
+                meta property="article:published_time" content=(post.metadata.date.to_rfc3339());
+                @if let Some(topic) = &post.metadata.topic {
+                    meta property="article:section" content=(topic);
+                }
+                @for tag in &post.metadata.tags {
+                    meta property="article:tag" content=(tag);
+                }
+                script type="application/ld+json" {
+                    (PreEscaped(blog_posting_json_ld(post, &canonical_url)))
+                }
+            }
+        }
+    }
+}
+
+fn blog_posting_json_ld(post: &Post, url: &str) -> String {
+    json!({
+        "@context": "https://schema.org",
+        "@type": "BlogPosting",
+        "headline": post.metadata.title,
+        "description": post.metadata.description,
+        "datePublished": post.metadata.date.to_rfc3339(),
+        "author": {
+            "@type": "Person",
+            "name": SITE_AUTHOR,
+        },
+        "keywords": post.metadata.tags.join(", "),
+        "url": url,
+        "mainEntityOfPage": url,
+    })
+    .to_string()
+    // The JSON ends up inside a <script> tag, so a "</script>" in any of the
+    // strings must not be able to close it early.
+    .replace("</", "<\\/")
 }
 
-pub fn wrap_content_into_full_page(app_name: &str, content: &str) -> String {
-    let html = INDEX_TEMPLATE.get_or_init(|| {
+/// Parses the post part of a url (`/posts/{route}`, `/graph/{route}`...).
+/// Anything that could point outside of the garden is logged and rejected,
+/// and the handlers answer it with a 404.
+pub fn parse_post_route(route: &str) -> Option<PostPath> {
+    match PostPath::from_route(route) {
+        Ok(path) => Some(path),
+        Err(e) => {
+            log::warn!("Rejected post path '{}': {}", route, e);
+            None
+        }
+    }
+}
+
+/// The value of a `web::block` job, or `None` once its failure, or the pool's,
+/// is logged, which the handlers answer with a 500.
+pub fn blocking_result<T, E: Display>(
+    result: Result<Result<T, E>, BlockingError>,
+    what: &str,
+) -> Option<T> {
+    match result {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(e)) => {
+            log::error!("Failed to {}: {}", what, e);
+            None
+        }
+        Err(e) => {
+            log::error!("Failed to {}: {}", what, e);
+            None
+        }
+    }
+}
+
+/// The page every full page render is wrapped into.
+pub fn index_template() -> &'static str {
+    INDEX_TEMPLATE.get_or_init(|| {
         let template_path =
             std::env::var("TEMPLATE_PATH").unwrap_or_else(|_| "./templates".to_string());
         std::fs::read_to_string(format!("{}/index.html", template_path))
             .expect("Failed to read index.html template")
-    });
+    })
+}
 
-    html.replace("{{APPNAME}}", app_name)
+pub fn wrap_content_into_full_page(app_state: &AppState, meta: &PageMeta, content: &str) -> String {
+    index_template()
+        .replace("{{HEAD}}", &meta.render_head(app_state).into_string())
+        .replace("{{APPNAME}}", &app_state.app_name)
         .replace("{{CONTENT}}", content)
 }
@@ -7,7 +7,7 @@ use maud::{PreEscaped, html};
 use music_module::{SpotifyUser, TopArtistsResponse, TopTracksResponse};
 use std::time::Duration;
 
-use crate::controllers::{AppState, wrap_content_into_full_page};
+use crate::controllers::{AppState, PageMeta, wrap_content_into_full_page};
 
 pub fn configure_services(cfg: &mut web::ServiceConfig) {
     cfg.service(get_user_profile)
@@ -35,8 +35,14 @@ async fn get_user_profile(app_state: web::Data<AppState>, req: HttpRequest) -> H
     if is_htmx_req {
         Html::new(html_to_render)
     } else {
+        let meta = PageMeta::new(
+            "Music",
+            "What I have been listening to lately on Spotify",
+            "/music/profile",
+        );
         Html::new(wrap_content_into_full_page(
-            &app_state.app_name,
+            &app_state,
+            &meta,
             &html_to_render.0,
         ))
     }
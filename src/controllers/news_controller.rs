@@ -1,16 +1,17 @@
-use crate::controllers::{AppState, wrap_content_into_full_page};
+use crate::controllers::{AppState, PageMeta, wrap_content_into_full_page};
+use crate::helpers::{format_date, format_relative_date};
 use actix_web::{
     HttpRequest, Responder, get,
     web::{self, Html},
 };
-use maud::html;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use maud::{Markup, html};
+use search_engine::types::{NewsFacets, NewsPage, NewsParams};
 
-#[derive(Serialize, Deserialize)]
 struct News {
     title: String,
     file_path: String,
-    date: String,
+    date: DateTime<Utc>,
     description: String,
     tags: Vec<String>,
 }
@@ -20,32 +21,24 @@ pub fn configure_services(cfg: &mut web::ServiceConfig) {
 }
 
 #[get("/news")]
-async fn news_page(app_state: web::Data<AppState>, req: HttpRequest) -> impl Responder {
-    let query = "SELECT 
-        file_path,
-        metadata.date AS date,
-        metadata.description AS description,
-        metadata.title AS title,
-        metadata.tags AS tags
-    FROM posts
-    ORDER BY date DESC";
-
-    let news: Vec<News> = app_state
-        .post_search_engine
-        .raw_query::<Vec<News>>(query)
-        .await
+async fn news_page(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    params: web::Query<NewsParams>,
+) -> impl Responder {
+    let results = app_state.post_search_engine.query_news(&params).await;
+    let facets = app_state.post_search_engine.get_news_facets().await;
+
+    let news: Vec<News> = results
+        .posts
         .iter()
-        .map(|n| {
-            let path = format!("/{}", n.file_path.replace(".md", ""));
-            News {
-                title: n.title.clone(),
-                file_path: path,
-                date: n.date.clone(),
-                description: n.description.clone(),
-                tags: n.tags.clone(),
-            }
+        .map(|p| News {
+            title: p.metadata.title.clone(),
+            file_path: format!("/{}", p.file_path.replace(".md", "")),
+            date: p.metadata.date,
+            description: p.metadata.description.clone(),
+            tags: p.metadata.tags.clone(),
         })
-        .clone()
         .collect();
 
     let is_empty = news.is_empty();
@@ -72,6 +65,8 @@ async fn news_page(app_state: web::Data<AppState>, req: HttpRequest) -> impl Res
                     }
                 }
 
+                (news_filters(&params, &facets))
+
                 @if is_empty {
                     div
                     class="text-center py-20"
@@ -93,15 +88,28 @@ async fn news_page(app_state: web::Data<AppState>, req: HttpRequest) -> impl Res
                                 {}
                             }
                         }
-                        h3
-                        class="text-xl font-semibold text-slate-900 dark:text-slate-100 mb-2"
-                        {
-                            "No posts yet"
-                        }
-                        p
-                        class="text-slate-600 dark:text-slate-400"
-                        {
-                            "Check back soon for new content!"
+                        @if params.has_filters() {
+                            h3
+                            class="text-xl font-semibold text-slate-900 dark:text-slate-100 mb-2"
+                            {
+                                "Nothing here"
+                            }
+                            p
+                            class="text-slate-600 dark:text-slate-400"
+                            {
+                                "No posts match these filters"
+                            }
+                        } @else {
+                            h3
+                            class="text-xl font-semibold text-slate-900 dark:text-slate-100 mb-2"
+                            {
+                                "No posts yet"
+                            }
+                            p
+                            class="text-slate-600 dark:text-slate-400"
+                            {
+                                "Check back soon for new content!"
+                            }
                         }
                     }
                 } @else {
@@ -174,8 +182,10 @@ async fn news_page(app_state: web::Data<AppState>, req: HttpRequest) -> impl Res
                                         }
                                         time
                                         class="text-sm md:text-base font-medium whitespace-nowrap"
+                                        datetime=(n.date.to_rfc3339())
+                                        title=(format_relative_date(&n.date))
                                         {
-                                            (n.date)
+                                            (format_date(&n.date))
                                         }
                                     }
                                 }
@@ -206,6 +216,8 @@ async fn news_page(app_state: web::Data<AppState>, req: HttpRequest) -> impl Res
                         }
                     }
                 }
+
+                    (news_pagination(&params, &results))
             }
         }
     }};
@@ -214,9 +226,171 @@ async fn news_page(app_state: web::Data<AppState>, req: HttpRequest) -> impl Res
     if is_htmx_req {
         Html::new(template)
     } else {
-        Html::new(wrap_content_into_full_page(
-            &app_state.app_name,
-            &template.0,
-        ))
+        let meta = PageMeta::new(
+            "Latest Updates",
+            "Discover my silly little adventures",
+            &news_url(&params),
+        );
+        Html::new(wrap_content_into_full_page(&app_state, &meta, &template.0))
+    }
+}
+
+fn news_url(params: &NewsParams) -> String {
+    match serde_urlencoded::to_string(params) {
+        Ok(query) if !query.is_empty() => format!("/news?{}", query),
+        _ => String::from("/news"),
+    }
+}
+
+fn filter_chip(label: &str, url: String, is_active: bool) -> Markup {
+    html! {
+        a
+        href=(url)
+        hx-target="#main-section"
+        hx-swap="innerHTML transition:true"
+        hx-push-url="true"
+        class=(if is_active {
+            "px-3 py-1 text-xs font-medium rounded-full bg-primary-color text-background-color"
+        } else {
+            "px-3 py-1 text-xs font-medium rounded-full bg-primary-color/10 text-primary-color hover:bg-primary-color/20 transition-colors"
+        })
+        {
+            (label)
+        }
+    }
+}
+
+fn news_filters(params: &NewsParams, facets: &NewsFacets) -> Markup {
+    // Selecting a filter always goes back to the first page, and clicking an
+    // active chip removes that filter.
+    let base = NewsParams {
+        page: None,
+        ..params.clone()
+    };
+
+    html! {
+        div
+        class="flex flex-col gap-3 mb-8"
+        {
+            @if facets.collections.len() > 1 {
+                div
+                class="flex flex-wrap items-center gap-2"
+                {
+                    span class="text-xs uppercase tracking-widest text-slate-500 w-16" { "Section" }
+                    @for collection in &facets.collections {
+                        @let is_active = params.collection.as_ref() == Some(collection);
+                        (filter_chip(collection, news_url(&NewsParams {
+                            collection: (!is_active).then(|| collection.clone()),
+                            ..base.clone()
+                        }), is_active))
+                    }
+                }
+            }
+            @if !facets.topics.is_empty() {
+                div
+                class="flex flex-wrap items-center gap-2"
+                {
+                    span class="text-xs uppercase tracking-widest text-slate-500 w-16" { "Topic" }
+                    @for topic in &facets.topics {
+                        @let is_active = params.topic.as_ref() == Some(topic);
+                        (filter_chip(topic, news_url(&NewsParams {
+                            topic: (!is_active).then(|| topic.clone()),
+                            ..base.clone()
+                        }), is_active))
+                    }
+                }
+            }
+            @if !facets.years.is_empty() {
+                div
+                class="flex flex-wrap items-center gap-2"
+                {
+                    span class="text-xs uppercase tracking-widest text-slate-500 w-16" { "Year" }
+                    @for year in &facets.years {
+                        @let is_active = params.year == Some(*year);
+                        (filter_chip(&year.to_string(), news_url(&NewsParams {
+                            year: (!is_active).then_some(*year),
+                            ..base.clone()
+                        }), is_active))
+                    }
+                }
+            }
+            @if !facets.tags.is_empty() {
+                div
+                class="flex flex-wrap items-center gap-2"
+                {
+                    span class="text-xs uppercase tracking-widest text-slate-500 w-16" { "Tag" }
+                    @for tag in &facets.tags {
+                        @let is_active = params.tag.as_ref() == Some(tag);
+                        (filter_chip(tag, news_url(&NewsParams {
+                            tag: (!is_active).then(|| tag.clone()),
+                            ..base.clone()
+                        }), is_active))
+                    }
+                }
+            }
+            @if params.has_filters() {
+                a
+                href=(news_url(&NewsParams {
+                    page_size: params.page_size,
+                    ..Default::default()
+                }))
+                hx-target="#main-section"
+                hx-swap="innerHTML transition:true"
+                hx-push-url="true"
+                class="self-start text-sm text-slate-500 hover:text-primary-color"
+                {
+                    "Clear filters"
+                }
+            }
+        }
+    }
+}
+
+fn news_pagination(params: &NewsParams, results: &NewsPage) -> Markup {
+    let page_link = |page: usize| {
+        news_url(&NewsParams {
+            page: Some(page),
+            ..params.clone()
+        })
+    };
+
+    html! {
+        @if results.total_pages() > 1 {
+            nav
+            class="flex items-center justify-between mt-8 text-sm"
+            {
+                @if results.has_previous() {
+                    a
+                    href=(page_link(results.page - 1))
+                    hx-target="#main-section"
+                    hx-swap="innerHTML transition:true"
+                    hx-push-url="true"
+                    class="hover:text-primary-color"
+                    {
+                        "← Newer posts"
+                    }
+                } @else {
+                    span {}
+                }
+                span
+                class="text-slate-500"
+                {
+                    "Page " (results.page) " of " (results.total_pages())
+                }
+                @if results.has_next() {
+                    a
+                    href=(page_link(results.page + 1))
+                    hx-target="#main-section"
+                    hx-swap="innerHTML transition:true"
+                    hx-push-url="true"
+                    class="hover:text-primary-color"
+                    {
+                        "Older posts →"
+                    }
+                } @else {
+                    span {}
+                }
+            }
+        }
     }
 }
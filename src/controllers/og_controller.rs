@@ -0,0 +1,31 @@
+use actix_web::{HttpResponse, get, web};
+
+use crate::controllers::{AppState, blocking_result, parse_post_route};
+use crate::helpers::og_image::get_or_render_og_image;
+
+pub fn configure_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(post_og_image);
+}
+
+#[get("/og/{post:.*}.png")]
+async fn post_og_image(app_state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    let Some(post_path) = parse_post_route(&path) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let Some(post) = app_state.post_search_engine.get_post(&post_path).await else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    let app_name = app_state.app_name.clone();
+    let reading_time = post.metadata.stats.reading_time(&app_state.reading_speed);
+    let png =
+        web::block(move || get_or_render_og_image(&app_name, &post.metadata, reading_time)).await;
+    let Some(png) = blocking_result(png, &format!("render OG image for {}", post_path)) else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header(("Cache-Control", "public, max-age=86400"))
+        .body(png)
+}
@@ -1,44 +1,79 @@
 use actix_web::{
-    HttpRequest, Responder, get,
+    HttpRequest, HttpResponse, HttpResponseBuilder, get,
+    http::header::{self, ContentType},
     web::{self, Data, Html},
 };
 use cached::proc_macro::cached;
-use markdown::{Constructs, Options, ParseOptions};
+use chrono::{DateTime, Utc};
 use maud::{PreEscaped, html};
 use search_engine::{
-    types::{DEFAULT_SEARCH_LIMIT, Params},
-    utils::Post,
+    stats::ReadingSpeed,
+    types::{DEFAULT_SEARCH_LIMIT, Params, RenderedPost, TocEntry},
+    utils::{Post, PostPath, content_hash, order_posts, render_hash},
 };
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
-use std::{fs, io};
+use std::sync::Arc;
 
-use crate::controllers::{AppState, wrap_content_into_full_page};
+use crate::controllers::{
+    AppState, PageMeta, fallback_controller::not_found_page, index_template, parse_post_route,
+    wrap_content_into_full_page,
+};
+use crate::helpers::{
+    format_date, format_relative_date,
+    garden::garden_config,
+    highlight::theme_css,
+    markdown::{render_fingerprint, render_indexed_post_with},
+};
 
 pub fn configure_services(cfg: &mut web::ServiceConfig) {
     cfg.service(landing)
         .service(search_post)
+        .service(syntax_theme)
+        // Before the post page, which would otherwise take `/history` as part of the path
+        .service(get_post_history)
         .service(get_post_page);
 }
 
 #[get("/")]
-async fn landing(app_state: web::Data<AppState>) -> impl Responder {
-    let content = match std::fs::read_to_string("./garden/welcome.md") {
-        Ok(content) => content,
-        Err(e) => {
-            log::error!("Failed to read welcome.md: {}", e);
-            return Html::new(wrap_content_into_full_page(
-                &app_state.app_name,
+async fn landing(app_state: web::Data<AppState>, req: HttpRequest) -> HttpResponse {
+    let landing_path = &garden_config().landing;
+    let Some(post) = app_state.post_search_engine.get_post(landing_path).await else {
+        log::error!(
+            "Failed to find the landing post {} in the index",
+            landing_path
+        );
+        return HttpResponse::Ok().content_type(ContentType::html()).body(
+            wrap_content_into_full_page(
+                &app_state,
+                &PageMeta::site_default("/"),
                 "<p>Error loading welcome page</p>",
-            ));
-        }
+            ),
+        );
+    };
+
+    let embedded = app_state.post_search_engine.get_embedded_posts(&post).await;
+    let translations = app_state.post_search_engine.get_translations(&post).await;
+    let validators = PostValidators::new(
+        &post,
+        &embedded,
+        &translations,
+        &app_state.reading_speed,
+        false,
+    );
+    if validators.matches(&req) {
+        return validators.not_modified();
+    }
+
+    let rendered_post = render_indexed_post_with(&app_state.post_search_engine, &post, &embedded);
+    let content =
+        post_page_shell(&rendered_post, &post, &translations, landing_path.route()).into_string();
+    let meta = PageMeta {
+        title: None,
+        ..PageMeta::for_post(post, "/")
     };
 
-    Html::new(wrap_content_into_full_page(
-        &app_state.app_name,
-        post_page_shell(content, "welcome".to_string())
-            .into_string()
-            .as_str(),
-    ))
+    validators.respond(wrap_content_into_full_page(&app_state, &meta, &content))
 }
 
 #[get("/posts/{post:.*}")]
@@ -46,33 +81,275 @@ async fn get_post_page(
     app_state: web::Data<AppState>,
     req: HttpRequest,
     route: web::Path<String>,
-) -> Html {
-    let content: io::Result<String> = fs::read_to_string(format!("./garden/{}.md", route));
+) -> HttpResponse {
+    post_page(&app_state, &req, &route).await
+}
+
+async fn post_page(app_state: &Data<AppState>, req: &HttpRequest, route: &str) -> HttpResponse {
+    let Some(post_path) = parse_post_route(route) else {
+        return not_found_page(app_state, req, route).await;
+    };
+    let Some(post) = app_state.post_search_engine.get_post(&post_path).await else {
+        if let Some(new_path) = app_state.post_search_engine.redirect_for(&post_path) {
+            return HttpResponse::MovedPermanently()
+                .insert_header((header::LOCATION, format!("/posts/{}", new_path.route())))
+                .finish();
+        }
+        return not_found_page(app_state, req, route).await;
+    };
 
     let is_htmx_req = req.headers().get("HX-Request").is_some();
+    let embedded = app_state.post_search_engine.get_embedded_posts(&post).await;
+    let translations = app_state.post_search_engine.get_translations(&post).await;
+    let validators = PostValidators::new(
+        &post,
+        &embedded,
+        &translations,
+        &app_state.reading_speed,
+        is_htmx_req,
+    );
+    if validators.matches(req) {
+        return validators.not_modified();
+    }
+
+    let rendered_post = render_indexed_post_with(&app_state.post_search_engine, &post, &embedded);
+    let content =
+        post_page_shell(&rendered_post, &post, &translations, post_path.route()).into_string();
+
     if is_htmx_req {
-        Html::new(match content {
-            Ok(md) => post_page_shell(md, route.to_string()).into_string(),
-            Err(_err) => String::from("Post not found"),
-        })
+        validators.respond(content)
+    } else {
+        let path = format!("/posts/{}", post_path.route());
+        validators.respond(wrap_content_into_full_page(
+            app_state,
+            &PageMeta::for_post(post, &path),
+            &content,
+        ))
+    }
+}
+
+#[get("/posts/{post:.*}/history")]
+async fn get_post_history(
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    route: web::Path<String>,
+) -> HttpResponse {
+    // A post that is itself called `history` wins over the history page
+    let full_route = format!("{}/history", route);
+    if let Ok(history_post) = PostPath::from_route(&full_route)
+        && app_state
+            .post_search_engine
+            .get_post(&history_post)
+            .await
+            .is_some()
+    {
+        return post_page(&app_state, &req, &full_route).await;
+    }
+
+    let Some(post_path) = parse_post_route(&route) else {
+        return not_found_page(&app_state, &req, &route).await;
+    };
+    let Some(post) = app_state.post_search_engine.get_post(&post_path).await else {
+        return not_found_page(&app_state, &req, &route).await;
+    };
+    // git runs for as long as the history of the post is long
+    let engine = Arc::clone(&app_state.post_search_engine);
+    let diff_post = post.clone();
+    let diffs = web::block(move || engine.revision_diffs(&diff_post))
+        .await
+        .unwrap_or_default();
+
+    let content = html! {
+        div
+        class="p-4 md:p-6 lg:p-8 overflow-auto w-full"
+        {
+            div
+            class="max-w-5xl mx-auto"
+            {
+                p class="text-primary-color text-sm font-semibold uppercase tracking-wider mb-2" { "History" }
+                h1 class="text-4xl font-bold mb-2" {
+                    a href=(format!("/posts/{}", post_path.route())) class="hover:text-primary-color" {
+                        (post.metadata.title)
+                    }
+                }
+                @if let (Some(created_at), Some(updated_at)) = (post.metadata.created_at(), post.metadata.updated_at()) {
+                    p class="text-zinc-400 mb-8" {
+                        "Written " (format_date(&created_at)) ", last updated " (format_relative_date(&updated_at)) "."
+                    }
+                }
+                @if post.metadata.revisions.is_empty() {
+                    p class="text-zinc-500 text-sm" { "No history is available for this post." }
+                }
+                ol class="flex flex-col gap-6" {
+                    @for revision in &post.metadata.revisions {
+                        li class="border border-shade-color" {
+                            div class="flex flex-wrap items-baseline gap-3 px-4 py-3 border-b border-shade-color" {
+                                code class="text-primary-color text-xs" { (revision.short_hash()) }
+                                p class="text-zinc-100 text-sm font-semibold flex-1" { (revision.message) }
+                                time class="text-zinc-500 text-xs" datetime=(revision.date.to_rfc3339()) {
+                                    (format_date(&revision.date))
+                                }
+                            }
+                            @if let Some(diff) = diffs.get(&revision.hash).filter(|diff| !diff.is_empty()) {
+                                details {
+                                    summary class="cursor-pointer px-4 py-2 text-xs text-zinc-500 hover:text-primary-color" { "Show changes" }
+                                    pre class="overflow-auto px-4 py-2 text-xs" {
+                                        @for line in diff_hunks(diff) {
+                                            @if line.starts_with('+') {
+                                                span class="block text-green-400" { (line) }
+                                            } @else if line.starts_with('-') {
+                                                span class="block text-red-400" { (line) }
+                                            } @else if line.starts_with("@@") {
+                                                span class="block text-zinc-500" { (line) }
+                                            } @else {
+                                                span class="block" { (line) }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    .into_string();
+
+    let body = if req.headers().get("HX-Request").is_some() {
+        content
     } else {
-        Html::new(match content {
-            Ok(md) => wrap_content_into_full_page(
-                &app_state.app_name,
-                post_page_shell(md, route.to_string())
-                    .into_string()
-                    .as_str(),
+        let meta = PageMeta::new(
+            &format!("History of {}", post.metadata.title),
+            &post.metadata.description,
+            &format!("/posts/{}/history", post_path.route()),
+        );
+        wrap_content_into_full_page(&app_state, &meta, &content)
+    };
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body)
+}
+
+// Skips the `diff --git`, `index` and `---`/`+++` headers, only the hunks are
+// interesting when reading the history of a single post
+fn diff_hunks(diff: &str) -> impl Iterator<Item = &str> {
+    diff.lines().skip_while(|line| !line.starts_with("@@"))
+}
+
+/// `ETag` and `Last-Modified` of a post response, so browsers and HTMX can
+/// revalidate instead of downloading the post again. Both change when the
+/// post, a note it embeds or one of its translations changes. The ETag also
+/// changes with the renderer and the reading speeds, and tells full pages and
+/// fragments apart.
+struct PostValidators {
+    etag: String,
+    last_modified: DateTime<Utc>,
+}
+
+impl PostValidators {
+    fn new(
+        post: &Post,
+        embedded: &HashMap<String, Post>,
+        translations: &[Post],
+        reading_speed: &ReadingSpeed,
+        is_htmx_req: bool,
+    ) -> PostValidators {
+        let mut hasher = Sha256::new();
+        hasher.update(render_fingerprint().as_bytes());
+        hasher.update([0]);
+        // Fragments are not wrapped in the page template
+        if !is_htmx_req {
+            hasher.update(index_template().as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update(render_hash(post, embedded).as_bytes());
+        hasher.update([0]);
+        hasher.update(
+            format!(
+                "{}-{}",
+                reading_speed.prose_words_per_minute, reading_speed.code_lines_per_minute
+            )
+            .as_bytes(),
+        );
+        for translation in translations {
+            hasher.update([0]);
+            hasher.update(content_hash(&translation.file_path, &translation.content).as_bytes());
+        }
+        let hash = format!("{:x}", hasher.finalize());
+
+        let last_modified = embedded
+            .values()
+            .chain(translations)
+            .map(|other| other.metadata.last_changed())
+            .fold(post.metadata.last_changed(), DateTime::max);
+
+        PostValidators {
+            etag: format!(
+                "\"{}-{}\"",
+                &hash[..16],
+                if is_htmx_req { "fragment" } else { "page" }
             ),
-            Err(_err) => String::from("Post not found"),
-        })
+            last_modified,
+        }
+    }
+
+    fn matches(&self, req: &HttpRequest) -> bool {
+        // If-Modified-Since is only used when there is no If-None-Match
+        if let Some(if_none_match) = req.headers().get(header::IF_NONE_MATCH) {
+            return if_none_match.to_str().is_ok_and(|etags| {
+                etags
+                    .split(',')
+                    .any(|etag| etag.trim() == "*" || etag.trim() == self.etag)
+            });
+        }
+
+        req.headers()
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|since| since.to_str().ok())
+            .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+            .is_some_and(|since| self.last_modified.timestamp() <= since.timestamp())
+    }
+
+    fn with_headers(&self, mut response: HttpResponseBuilder) -> HttpResponseBuilder {
+        response
+            .insert_header((header::ETAG, self.etag.clone()))
+            .insert_header((
+                header::LAST_MODIFIED,
+                self.last_modified
+                    .format("%a, %d %b %Y %H:%M:%S GMT")
+                    .to_string(),
+            ))
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .insert_header((header::VARY, "HX-Request"));
+        response
+    }
+
+    fn not_modified(&self) -> HttpResponse {
+        self.with_headers(HttpResponse::NotModified()).finish()
+    }
+
+    fn respond(&self, body: String) -> HttpResponse {
+        self.with_headers(HttpResponse::Ok())
+            .content_type(ContentType::html())
+            .body(body)
     }
 }
 
+#[get("/syntax.css")]
+async fn syntax_theme() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/css; charset=utf-8")
+        .insert_header(("Cache-Control", "public, max-age=86400"))
+        .body(theme_css())
+}
+
 #[cached(
     key = "String",
     convert = r##"{ 
         format!(
-            "{}:{}",
+            "{}:{}:{}:{}",
             params
                 .clone()
                 .query
@@ -83,7 +360,9 @@ async fn get_post_page(
                 .limit
                 .clone()
                 .unwrap_or(DEFAULT_SEARCH_LIMIT)
-                .value()
+                .value(),
+            params.collection.clone().unwrap_or_default(),
+            params.lang.map(|lang| lang.code()).unwrap_or_default()
         )
     }"##
 )]
@@ -116,53 +395,12 @@ async fn search_post(
 }
 
 async fn get_default_posts(app_state: Data<AppState>) -> Vec<Post> {
-    let posts_to_search = [
-        "welcome.md",
-        "hello.md",
-        "garden_styling.md",
-        "kilbarrack.md",
-        "first_job_in_ireland.md",
-        "rathmines.md",
-    ];
-    let default_posts_string = posts_to_search
-        .iter()
-        .map(|file_name| format!("'{}'", file_name))
-        .collect::<Vec<String>>()
-        .join(", ");
-
-    let default_posts = app_state
-        .post_search_engine
-        .raw_query::<Vec<Post>>(
-            format!(
-                "SELECT * FROM posts WHERE file_name AND metadata.is_draft = false IN [{}]",
-                default_posts_string,
-            )
-            .as_str(),
-        )
-        .await;
-
-    let posts_map: HashMap<String, Post> = default_posts
-        .into_iter()
-        .map(|f| (f.file_name.to_string(), f))
-        .collect();
-
-    let ordered_files: Vec<Post> = posts_to_search
-        .iter()
-        .filter_map(|default_doc| posts_map.get(*default_doc).cloned())
-        .collect();
-
-    let all_missing_posts: Vec<Post> = app_state
-        .post_search_engine
-        .raw_query::<Vec<Post>>(
-            format!(
-                "SELECT * FROM posts WHERE file_name NOT IN [{}]",
-                default_posts_string,
-            )
-            .as_str(),
-        )
-        .await;
-
-    [ordered_files, all_missing_posts].concat()
+    let config = garden_config();
+    order_posts(
+        app_state.post_search_engine.get_all_posts().await,
+        &config.pinned,
+        config.sort,
+    )
 }
 
 fn build_posts_list(matching_posts: Vec<Post>) -> Html {
@@ -255,23 +493,21 @@ fn build_posts_list(matching_posts: Vec<Post>) -> Html {
     Html::new(html)
 }
 
-fn post_page_shell(md: String, post_path: String) -> PreEscaped<String> {
-    let frontmatter = Options {
-        parse: ParseOptions {
-            constructs: Constructs {
-                frontmatter: true,
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-        ..Default::default()
-    };
-
+fn post_page_shell(
+    rendered_post: &RenderedPost,
+    post: &Post,
+    translations: &[Post],
+    post_path: &str,
+) -> PreEscaped<String> {
     html! {
         div
+        lang=(post.metadata.lang.code())
         class="prose prose-theme w-full max-w-full p-4 md:p-6 lg:p-8 overflow-auto text-sm md:text-base"
             {
-               (PreEscaped(markdown::to_html_with_options(&md, &frontmatter).unwrap()))
+               @if !translations.is_empty() {
+                   (language_switcher(post, translations))
+               }
+               (PreEscaped(&rendered_post.html))
             }
         div
         id="right-section"
@@ -289,6 +525,9 @@ fn post_page_shell(md: String, post_path: String) -> PreEscaped<String> {
                 class="flex w-full flex-grow cursor-grab active:cursor-grabbing h-full"
                 {}
             }
+            @if rendered_post.toc.len() > 1 {
+                (table_of_contents(&rendered_post.toc))
+            }
             div
             class="flex flex-col flex-grow w-full"
             {
@@ -306,3 +545,109 @@ fn post_page_shell(md: String, post_path: String) -> PreEscaped<String> {
         }
     }
 }
+
+fn language_switcher(post: &Post, translations: &[Post]) -> PreEscaped<String> {
+    html! {
+        nav
+        aria-label="Translations"
+        class="not-prose flex flex-wrap items-center gap-2 mb-4 text-xs"
+        {
+            span class="px-3 py-1 font-medium rounded-full bg-primary-color text-background-color" {
+                (post.metadata.lang.native_name())
+            }
+            @for translation in translations {
+                @if let Ok(translation_path) = PostPath::from_file_path(&translation.file_path) {
+                    a
+                    href=(format!("/posts/{}", translation_path.route()))
+                    hreflang=(translation.metadata.lang.code())
+                    lang=(translation.metadata.lang.code())
+                    hx-target="#main-section"
+                    hx-swap="innerHTML transition:true"
+                    hx-push-url="true"
+                    class="px-3 py-1 font-medium rounded-full bg-primary-color/10 text-primary-color hover:bg-primary-color/20 transition-colors"
+                    {
+                        (translation.metadata.lang.native_name())
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn table_of_contents(toc: &[TocEntry]) -> PreEscaped<String> {
+    // Indent relative to the shallowest heading, posts don't always start at h1
+    let min_depth = toc.iter().map(|entry| entry.depth).min().unwrap_or(1);
+
+    html! {
+        nav
+        id="table-of-contents"
+        class="sticky top-0 flex flex-col gap-2 w-full max-h-[30vh] overflow-auto p-2 border-b border-shade-color"
+        {
+            div class="flex items-center gap-3 w-full" {
+                p class="text-primary-color text-sm font-semibold uppercase tracking-wider" { "On this page" }
+                div class="flex-1 h-px bg-shade-color" {}
+            }
+            ul class="flex flex-col gap-1 text-sm" {
+                @for entry in toc {
+                    li style=(format!("padding-left: {}rem", (entry.depth - min_depth) as f32 * 0.75)) {
+                        a
+                        href=(format!("#{}", entry.id))
+                        title=(entry.text)
+                        class="block truncate hover:text-primary-color"
+                        {
+                            (entry.text)
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use search_engine::utils::MdMetadata;
+
+    fn post(file_path: &str, content: &str) -> Post {
+        Post {
+            file_name: file_path.rsplit('/').next().unwrap().to_string(),
+            file_path: file_path.to_string(),
+            collection: String::from("garden"),
+            metadata: MdMetadata {
+                title: file_path.to_string(),
+                topic: None,
+                description: String::new(),
+                tags: Vec::new(),
+                date: Utc::now(),
+                is_draft: false,
+                post_source_url: String::new(),
+                stats: Default::default(),
+                aliases: Vec::new(),
+                revisions: Vec::new(),
+                lang: Default::default(),
+                translations: Vec::new(),
+                pinned: false,
+                weight: None,
+            },
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_etag_changes_with_translations() {
+        let rathmines = post("journal/rathmines.md", "Hello");
+        let mut translation = post("journal/rathmines_es.md", "Hola");
+        let speed = ReadingSpeed::default();
+        let validators = |translations: &[Post]| {
+            PostValidators::new(&rathmines, &HashMap::new(), translations, &speed, true)
+        };
+
+        let before = validators(std::slice::from_ref(&translation)).etag;
+        assert_eq!(validators(std::slice::from_ref(&translation)).etag, before);
+
+        translation.content = String::from("Hola de nuevo");
+        assert_ne!(validators(std::slice::from_ref(&translation)).etag, before);
+        assert_ne!(validators(&[]).etag, before);
+    }
+}
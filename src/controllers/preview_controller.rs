@@ -0,0 +1,72 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    web::{self, Html},
+};
+use maud::html;
+
+use crate::controllers::{AppState, parse_post_route};
+use crate::helpers::markdown::render_indexed_post;
+
+const EXCERPT_MAX_CHARS: usize = 280;
+
+pub fn configure_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(post_preview);
+}
+
+// Rendering is the expensive part, and the engine already caches it by the
+// content hash of the post
+#[get("/preview/{post:.*}")]
+async fn post_preview(
+    app_state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> (Html, StatusCode) {
+    let Some(post_path) = parse_post_route(&path) else {
+        return (Html::new(""), StatusCode::NOT_FOUND);
+    };
+    let Some(post) = app_state.post_search_engine.get_post(&post_path).await else {
+        return (
+            Html::new(html! {
+                p class="text-zinc-500 text-sm" { "No preview available for this post." }
+            }),
+            StatusCode::OK,
+        );
+    };
+
+    let excerpt = render_indexed_post(&app_state.post_search_engine, &post)
+        .await
+        .excerpt
+        .as_deref()
+        .map(truncate);
+
+    let preview = Html::new(html! {
+        div class="flex flex-col gap-2" {
+            div class="flex items-center gap-3 w-full" {
+                p class="text-primary-color text-xs font-semibold uppercase tracking-wider" {
+                    (post.metadata.topic.as_deref().unwrap_or("Post"))
+                }
+                div class="flex-1 h-px bg-shade-color" {}
+                p class="text-zinc-500 text-xs" { (post.metadata.stats.reading_time(&app_state.reading_speed)) " min read" }
+            }
+            p class="text-bright-color text-base font-semibold" { (post.metadata.title) }
+            p class="text-zinc-400 text-sm" { (post.metadata.description) }
+            @if let Some(excerpt) = excerpt {
+                p class="text-sm border-l-2 border-shade-color pl-3" { (excerpt) }
+            }
+        }
+    });
+
+    (preview, StatusCode::OK)
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= EXCERPT_MAX_CHARS {
+        return text.to_string();
+    }
+
+    let truncated: String = text.chars().take(EXCERPT_MAX_CHARS).collect();
+    match truncated.rsplit_once(' ') {
+        Some((words, _)) => format!("{}…", words),
+        None => format!("{}…", truncated),
+    }
+}
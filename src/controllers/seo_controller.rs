@@ -0,0 +1,117 @@
+use actix_web::{HttpResponse, get, web};
+use chrono::{DateTime, Utc};
+use maud::{PreEscaped, html};
+use std::collections::BTreeMap;
+
+use crate::controllers::AppState;
+use crate::helpers::garden::garden_config;
+
+// Pages that are not backed by a post. They change on their own (chess games,
+// music, Steam activity), so they are listed without a lastmod.
+const STATIC_PAGES: [&str; 5] = [
+    "/news",
+    "/garden-view-dispatcher",
+    "/chess/stats/rapid",
+    "/music/profile",
+    "/steam",
+];
+
+pub fn configure_services(cfg: &mut web::ServiceConfig) {
+    cfg.service(sitemap).service(robots);
+}
+
+struct SitemapEntry {
+    loc: String,
+    lastmod: Option<DateTime<Utc>>,
+}
+
+#[get("/sitemap.xml")]
+async fn sitemap(app_state: web::Data<AppState>) -> HttpResponse {
+    let posts = app_state.post_search_engine.get_all_posts().await;
+    let site_url = &app_state.site_url;
+
+    let mut entries: Vec<SitemapEntry> = vec![SitemapEntry {
+        loc: format!("{}/", site_url),
+        lastmod: posts
+            .iter()
+            .find(|p| p.file_path == garden_config().landing.as_str())
+            .map(|post| post.metadata.last_changed()),
+    }];
+
+    let mut tags: BTreeMap<&str, DateTime<Utc>> = BTreeMap::new();
+    let mut topics: BTreeMap<&str, DateTime<Utc>> = BTreeMap::new();
+    for post in &posts {
+        entries.push(SitemapEntry {
+            loc: format!(
+                "{}/posts/{}",
+                site_url,
+                post.file_path
+                    .strip_suffix(".md")
+                    .unwrap_or(&post.file_path)
+            ),
+            lastmod: Some(post.metadata.last_changed()),
+        });
+
+        // A tag or topic changes whenever any of its posts does
+        let changed = post.metadata.last_changed();
+        for tag in &post.metadata.tags {
+            tags.entry(tag)
+                .and_modify(|lastmod| *lastmod = (*lastmod).max(changed))
+                .or_insert(changed);
+        }
+        if let Some(topic) = &post.metadata.topic {
+            topics
+                .entry(topic)
+                .and_modify(|lastmod| *lastmod = (*lastmod).max(changed))
+                .or_insert(changed);
+        }
+    }
+
+    for (filter, values) in [("tag", tags), ("topic", topics)] {
+        for (value, lastmod) in values {
+            entries.push(SitemapEntry {
+                loc: format!(
+                    "{}/news?{}",
+                    site_url,
+                    serde_urlencoded::to_string([(filter, value)]).unwrap_or_default()
+                ),
+                lastmod: Some(lastmod),
+            });
+        }
+    }
+
+    entries.extend(STATIC_PAGES.iter().map(|page| SitemapEntry {
+        loc: format!("{}{}", site_url, page),
+        lastmod: None,
+    }));
+
+    let xml = html! {
+        (PreEscaped(r#"<?xml version="1.0" encoding="UTF-8"?>"#))
+        urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" {
+            @for entry in entries {
+                url {
+                    loc { (entry.loc) }
+                    @if let Some(lastmod) = entry.lastmod {
+                        lastmod { (lastmod.format("%Y-%m-%d")) }
+                    }
+                }
+            }
+        }
+    };
+
+    HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(xml.into_string())
+}
+
+#[get("/robots.txt")]
+async fn robots(app_state: web::Data<AppState>) -> HttpResponse {
+    let template_path =
+        std::env::var("TEMPLATE_PATH").unwrap_or_else(|_| "./templates".to_string());
+    let robots = std::fs::read_to_string(format!("{}/robots.txt", template_path))
+        .unwrap_or_else(|_| String::from("User-agent: *\nAllow: /\n"));
+
+    HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(robots.replace("{{SITE_URL}}", &app_state.site_url))
+}
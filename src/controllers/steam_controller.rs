@@ -7,7 +7,7 @@ use games_module::{RecentGame, SteamProfile, TopGame};
 use maud::{PreEscaped, html};
 use std::time::Duration;
 
-use crate::controllers::{AppState, wrap_content_into_full_page};
+use crate::controllers::{AppState, PageMeta, wrap_content_into_full_page};
 
 pub fn configure_services(cfg: &mut web::ServiceConfig) {
     cfg.service(steam_page);
@@ -43,8 +43,14 @@ async fn steam_page(app_state: web::Data<AppState>, req: HttpRequest) -> Html {
     if is_htmx_req {
         Html::new(html_to_render)
     } else {
+        let meta = PageMeta::new(
+            "Games",
+            "My Steam profile, recently played and most played games",
+            "/steam",
+        );
         Html::new(wrap_content_into_full_page(
-            &app_state.app_name,
+            &app_state,
+            &meta,
             &html_to_render.0,
         ))
     }
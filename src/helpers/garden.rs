@@ -0,0 +1,180 @@
+use search_engine::{
+    types::{Collection, Collections, PostSort},
+    utils::{PostPath, parse_alias},
+};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::PathBuf, sync::OnceLock};
+
+static GARDEN_CONFIG: OnceLock<GardenConfig> = OnceLock::new();
+
+/// How the garden is laid out, read from the file at `GARDEN_CONFIG_PATH`
+/// (`./garden.toml` by default) when the server starts.
+pub struct GardenConfig {
+    /// Post shown on `/`.
+    pub landing: PostPath,
+    /// Posts listed first in the sidebar, in this order.
+    pub pinned: Vec<PostPath>,
+    /// Order of every other post in the sidebar.
+    pub sort: PostSort,
+    pub collections: Collections,
+}
+
+// Before garden.toml, collections had a file of their own
+#[derive(Deserialize)]
+struct CollectionsFile {
+    collections: Vec<Collection>,
+}
+
+#[derive(Deserialize)]
+struct GardenFile {
+    landing: Option<String>,
+    #[serde(default)]
+    pinned: Vec<String>,
+    #[serde(default)]
+    sort: PostSort,
+    #[serde(default)]
+    collections: Vec<Collection>,
+}
+
+impl GardenConfig {
+    fn load(env_vars: &HashMap<String, String>) -> GardenConfig {
+        let path = env_vars
+            .get("GARDEN_CONFIG_PATH")
+            .map(String::as_str)
+            .unwrap_or("./garden.toml");
+        let collections_path = env_vars
+            .get("COLLECTIONS_PATH")
+            .map(String::as_str)
+            .unwrap_or("./collections.toml");
+
+        match (
+            fs::read_to_string(path),
+            fs::read_to_string(collections_path),
+        ) {
+            (Ok(content), Ok(_)) => {
+                log::warn!(
+                    "Ignoring the collections in {}, they are read from {} now",
+                    collections_path,
+                    path
+                );
+                GardenConfig::from_file(path, &content)
+            }
+            (Ok(content), Err(_)) => GardenConfig::from_file(path, &content),
+            (Err(_), Ok(content)) => {
+                log::warn!(
+                    "{} (COLLECTIONS_PATH) is deprecated and will stop being read, move its collections to {}",
+                    collections_path,
+                    path
+                );
+                GardenConfig::from_collections_file(collections_path, &content)
+            }
+            (Err(_), Err(_)) => GardenConfig::default(),
+        }
+    }
+
+    fn from_collections_file(path: &str, content: &str) -> GardenConfig {
+        let file: CollectionsFile = toml::from_str(content)
+            .unwrap_or_else(|e| panic!("Invalid collections in {}: {}", path, e));
+
+        GardenConfig {
+            collections: Collections::new(file.collections),
+            ..GardenConfig::default()
+        }
+    }
+
+    fn from_file(path: &str, content: &str) -> GardenConfig {
+        let file: GardenFile = toml::from_str(content)
+            .unwrap_or_else(|e| panic!("Invalid garden config in {}: {}", path, e));
+        let post_path = |route: &str| {
+            parse_alias(route).unwrap_or_else(|e| panic!("Invalid post in {}: {}", path, e))
+        };
+
+        GardenConfig {
+            landing: file
+                .landing
+                .as_deref()
+                .map(post_path)
+                .unwrap_or_else(default_landing),
+            pinned: file.pinned.iter().map(|route| post_path(route)).collect(),
+            sort: file.sort,
+            collections: match file.collections.is_empty() {
+                true => Collections::default(),
+                false => Collections::new(file.collections),
+            },
+        }
+    }
+}
+
+impl Default for GardenConfig {
+    /// The whole `./garden` folder as a single collection, landing on
+    /// `welcome.md`.
+    fn default() -> Self {
+        GardenConfig {
+            landing: default_landing(),
+            pinned: Vec::new(),
+            sort: PostSort::default(),
+            collections: Collections::default(),
+        }
+    }
+}
+
+fn default_landing() -> PostPath {
+    PostPath::from_file_path("welcome.md").unwrap()
+}
+
+/// Reads the garden config with the variables of the env file, it has to run
+/// before anything asks for [`garden_config`].
+pub fn load_garden_config(env_vars: &HashMap<String, String>) -> &'static GardenConfig {
+    GARDEN_CONFIG.get_or_init(|| GardenConfig::load(env_vars))
+}
+
+pub fn garden_config() -> &'static GardenConfig {
+    GARDEN_CONFIG
+        .get()
+        .expect("The garden config is loaded before the server starts")
+}
+
+/// The collections the garden is made of.
+pub fn collections() -> &'static Collections {
+    &garden_config().collections
+}
+
+/// Where a garden path (`journal/2025/rathmines.md`) is on disk.
+pub fn disk_path(file_path: &str) -> Option<PathBuf> {
+    collections().disk_path(file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_the_garden_config() {
+        let config = GardenConfig::from_file("garden.toml", include_str!("../../garden.toml"));
+
+        assert_eq!(config.landing.as_str(), "welcome.md");
+        assert_eq!(config.pinned[3].as_str(), "journal/2025/kilbarrack.md");
+        assert_eq!(config.sort, PostSort::Date);
+        assert_eq!(config.collections.names(), ["notes", "journal", "gaming"]);
+    }
+
+    #[test]
+    fn test_reads_the_deprecated_collections_file() {
+        let config = GardenConfig::from_collections_file(
+            "collections.toml",
+            "[[collections]]\nname = \"journal\"\nroot = \"./garden/journal\"\nprefix = \"journal\"\n",
+        );
+
+        assert_eq!(config.landing.as_str(), "welcome.md");
+        assert_eq!(config.collections.names(), ["journal"]);
+    }
+
+    #[test]
+    fn test_missing_fields_use_the_defaults() {
+        let config = GardenConfig::from_file("garden.toml", "sort = \"title\"");
+
+        assert_eq!(config.landing.as_str(), "welcome.md");
+        assert!(config.pinned.is_empty());
+        assert_eq!(config.collections.names(), ["garden"]);
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use syntect::{
+    highlighting::{Theme, ThemeSet},
+    html::{ClassStyle, css_for_theme_with_class_style, line_tokens_to_classed_spans},
+    parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+const CLASS_PREFIX: &str = "hl-";
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed {
+    prefix: CLASS_PREFIX,
+};
+const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+static THEME_CSS: OnceLock<String> = OnceLock::new();
+
+fn syntax_set() -> &'static SyntaxSet {
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// `SYNTAX_THEME` is either the name of one of syntect's bundled themes
+/// (`base16-ocean.dark`, `InspiredGitHub`, `Solarized (dark)`...) or a path
+/// to a `.tmTheme` file.
+fn load_theme() -> Theme {
+    let theme_name = std::env::var("SYNTAX_THEME").unwrap_or_else(|_| DEFAULT_THEME.to_string());
+
+    if theme_name.ends_with(".tmTheme") {
+        match ThemeSet::get_theme(&theme_name) {
+            Ok(theme) => return theme,
+            Err(e) => log::error!("Failed to load syntax theme {}: {}", theme_name, e),
+        }
+    }
+
+    let mut themes = ThemeSet::load_defaults().themes;
+    match themes.remove(&theme_name) {
+        Some(theme) => theme,
+        None => {
+            log::error!(
+                "Unknown syntax theme {}, falling back to {}",
+                theme_name,
+                DEFAULT_THEME
+            );
+            themes.remove(DEFAULT_THEME).unwrap()
+        }
+    }
+}
+
+/// The stylesheet for the highlighted spans, generated from the configured theme.
+pub fn theme_css() -> &'static str {
+    THEME_CSS.get_or_init(|| {
+        css_for_theme_with_class_style(&load_theme(), CLASS_STYLE).unwrap_or_else(|e| {
+            log::error!("Failed to generate syntax theme css: {}", e);
+            String::new()
+        })
+    })
+}
+
+/// Options read from the info string of a code fence, e.g.
+/// ```` ```rust {3-5} showLineNumbers ````.
+#[derive(Default)]
+struct FenceOptions {
+    highlighted_lines: HashSet<usize>,
+    line_numbers: bool,
+}
+
+impl FenceOptions {
+    fn parse(meta: Option<&str>) -> FenceOptions {
+        let mut options = FenceOptions::default();
+
+        for token in meta.unwrap_or_default().split_whitespace() {
+            if token == "showLineNumbers" || token == "linenos" {
+                options.line_numbers = true;
+            } else if let Some(ranges) = token.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+                options.highlighted_lines.extend(parse_line_ranges(ranges));
+            }
+        }
+
+        options
+    }
+}
+
+// "1,3-5" => {1, 3, 4, 5}. Anything that doesn't parse is ignored.
+fn parse_line_ranges(ranges: &str) -> Vec<usize> {
+    ranges
+        .split(',')
+        .flat_map(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            match (start.trim().parse::<usize>(), end.trim().parse::<usize>()) {
+                (Ok(start), Ok(end)) if start <= end => (start..=end).collect(),
+                _ => Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// Renders a code block to HTML, with every token wrapped in a `hl-*` classed
+/// span. Languages syntect doesn't know are still rendered line by line so
+/// line numbers and highlighting work for them too.
+pub fn highlight_code_block(code: &str, lang: Option<&str>, meta: Option<&str>) -> String {
+    let options = FenceOptions::parse(meta);
+    let syntax = lang.and_then(|lang| syntax_set().find_syntax_by_token(lang));
+
+    let lines = match syntax {
+        Some(syntax) => highlighted_lines(code, syntax),
+        None => None,
+    }
+    .unwrap_or_else(|| plain_lines(code));
+
+    let mut pre_classes = vec![format!("{}code", CLASS_PREFIX)];
+    if options.line_numbers {
+        pre_classes.push(String::from("line-numbers"));
+    }
+    let lang_class = lang
+        .map(|lang| format!(r#" class="language-{}""#, escape_attribute(lang)))
+        .unwrap_or_default();
+
+    let mut html = format!(
+        r#"<pre class="{}"><code{}>"#,
+        pre_classes.join(" "),
+        lang_class
+    );
+    for (index, line) in lines.iter().enumerate() {
+        let line_class = if options.highlighted_lines.contains(&(index + 1)) {
+            "line highlighted"
+        } else {
+            "line"
+        };
+        html.push_str(&format!(r#"<span class="{}">{}</span>"#, line_class, line));
+        html.push('\n');
+    }
+    html.push_str("</code></pre>\n");
+
+    html
+}
+
+// Each line is closed on its own, so the spans still open at the end of a line
+// are closed there and opened again at the start of the next one.
+fn highlighted_lines(code: &str, syntax: &SyntaxReference) -> Option<Vec<String>> {
+    let mut parse_state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut lines = Vec::new();
+
+    for line in LinesWithEndings::from(code) {
+        let ops = parse_state.parse_line(line, syntax_set()).ok()?;
+
+        let reopened: String = stack
+            .as_slice()
+            .iter()
+            .map(|scope| format!(r#"<span class="{}">"#, scope_classes(scope)))
+            .collect();
+        let (spans, _) = line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, &mut stack).ok()?;
+
+        // The line ending is added back around the line's own span
+        lines.push(format!(
+            "{}{}{}",
+            reopened,
+            spans.replace(['\n', '\r'], ""),
+            "</span>".repeat(stack.len())
+        ));
+    }
+
+    Some(lines)
+}
+
+fn plain_lines(code: &str) -> Vec<String> {
+    code.lines().map(escape_html).collect()
+}
+
+fn scope_classes(scope: &Scope) -> String {
+    scope
+        .build_string()
+        .split('.')
+        .map(|atom| format!("{}{}", CLASS_PREFIX, atom))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn escape_attribute(text: &str) -> String {
+    escape_html(text).replace('"', "&quot;")
+}
@@ -0,0 +1,207 @@
+use image::{ImageFormat, imageops::FilterType};
+use percent_encoding::percent_decode_str;
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    io::Cursor,
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::helpers::garden::disk_path;
+
+/// Widths the resized variants are generated at, any requested width is
+/// rounded up to one of these so the cache can't grow without bounds.
+pub const VARIANT_WIDTHS: [u32; 5] = [320, 640, 960, 1280, 1920];
+// Bump this whenever the resizing changes so old cached variants are not served
+const VARIANT_VERSION: &str = "1";
+
+static IMG_TAG: OnceLock<Regex> = OnceLock::new();
+
+fn cache_dir() -> PathBuf {
+    PathBuf::from(
+        std::env::var("IMAGE_CACHE_PATH").unwrap_or_else(|_| "./cache/images".to_string()),
+    )
+}
+
+/// Joins a path relative to the garden root, refusing anything that would end
+/// up outside of it.
+pub fn garden_relative_path(path: &str) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(resolved)
+}
+
+/// Rewrites the images of a rendered post: sources relative to the post are
+/// pointed to its URL, and co-located images get their size and smaller WebP
+/// variants. Every image is lazy loaded.
+pub fn rewrite_images(html: &str, source_path: &str) -> String {
+    let img_tag = IMG_TAG.get_or_init(|| Regex::new(r#"<img src="([^"]*)"([^>]*?)\s*/>"#).unwrap());
+    let post_dir = Path::new(source_path).parent().unwrap_or(Path::new(""));
+
+    img_tag
+        .replace_all(html, |caps: &Captures| {
+            let src = &caps[1];
+            let attributes = &caps[2];
+
+            // The URL keeps the source as it was written (percent and HTML
+            // encoded), the file path is the decoded one.
+            let url = is_relative_url(src)
+                .then(|| garden_relative_path(&post_dir.join(src).to_string_lossy()))
+                .flatten()
+                .map(|path| format!("/posts/{}", path.to_string_lossy()));
+            let attachment = url.as_ref().and_then(|_| {
+                let decoded = percent_decode_str(&src.replace("&amp;", "&"))
+                    .decode_utf8_lossy()
+                    .to_string();
+                garden_relative_path(&post_dir.join(decoded).to_string_lossy())
+            });
+
+            let (Some(url), Some(attachment)) = (url, attachment) else {
+                return format!(
+                    r#"<img src="{}"{} loading="lazy" decoding="async" />"#,
+                    src, attributes
+                );
+            };
+
+            let size = disk_path(&attachment.to_string_lossy())
+                .and_then(|path| image::image_dimensions(path).ok());
+            let mut extra = String::new();
+            if let Some((width, height)) = size {
+                extra.push_str(&format!(r#" width="{}" height="{}""#, width, height));
+
+                let variants: Vec<String> = VARIANT_WIDTHS
+                    .iter()
+                    .filter(|variant| **variant < width)
+                    .map(|variant| format!("{}?w={}&amp;format=webp {}w", url, variant, variant))
+                    .chain([format!("{}?format=webp {}w", url, width)])
+                    .collect();
+                if is_resizable(&attachment) && variants.len() > 1 {
+                    extra.push_str(&format!(
+                        r#" srcset="{}" sizes="(min-width: 1024px) 65ch, 100vw""#,
+                        variants.join(", ")
+                    ));
+                }
+            }
+
+            format!(
+                r#"<img src="{}"{}{} loading="lazy" decoding="async" />"#,
+                url, attributes, extra
+            )
+        })
+        .into_owned()
+}
+
+fn is_relative_url(src: &str) -> bool {
+    !(src.is_empty()
+        || src.starts_with('/')
+        || src.starts_with('#')
+        || src.contains("://")
+        || src.starts_with("data:")
+        || src.starts_with("mailto:"))
+}
+
+/// Whether resized variants can be generated for the image. Vector and
+/// animated images are always served as they are.
+pub fn is_resizable(path: &Path) -> bool {
+    matches!(
+        ImageFormat::from_path(path),
+        Ok(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP)
+    )
+}
+
+/// Returns the image resized to the given width and/or converted to WebP,
+/// rendering it only if the cached variant for the current file does not
+/// exist yet. The content type of the variant is returned with it.
+pub fn get_or_render_variant(
+    path: &Path,
+    width: Option<u32>,
+    webp: bool,
+) -> io::Result<(Vec<u8>, &'static str)> {
+    let source_format = ImageFormat::from_path(path).map_err(io::Error::other)?;
+    let format = if webp {
+        ImageFormat::WebP
+    } else {
+        source_format
+    };
+    let width = width.map(|width| {
+        VARIANT_WIDTHS
+            .into_iter()
+            .find(|variant| *variant >= width)
+            .unwrap_or(VARIANT_WIDTHS[VARIANT_WIDTHS.len() - 1])
+    });
+
+    let cached_path = cache_dir().join(format!(
+        "{}.{}",
+        variant_hash(path, width, format)?,
+        format.extensions_str()[0]
+    ));
+    if let Ok(bytes) = fs::read(&cached_path) {
+        return Ok((bytes, format.to_mime_type()));
+    }
+
+    let mut image = image::open(path).map_err(io::Error::other)?;
+    if let Some(width) = width
+        && width < image.width()
+    {
+        image = image.resize(width, u32::MAX, FilterType::Lanczos3);
+    }
+    // The WebP encoder doesn't take 16-bit images
+    if format == ImageFormat::WebP {
+        image = image::DynamicImage::ImageRgba8(image.to_rgba8());
+    }
+
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), format)
+        .map_err(io::Error::other)?;
+
+    if let Err(e) = fs::create_dir_all(cache_dir()).and_then(|_| fs::write(&cached_path, &bytes)) {
+        log::warn!(
+            "Could not cache image variant {}: {}",
+            cached_path.display(),
+            e
+        );
+    }
+
+    Ok((bytes, format.to_mime_type()))
+}
+
+// The file's size and modification time are part of the key, so replacing an
+// image in the garden renders its variants again
+fn variant_hash(path: &Path, width: Option<u32>, format: ImageFormat) -> io::Result<String> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut hasher = Sha256::new();
+    for field in [
+        VARIANT_VERSION.to_string(),
+        path.to_string_lossy().to_string(),
+        metadata.len().to_string(),
+        modified.to_string(),
+        width.map(|w| w.to_string()).unwrap_or_default(),
+        format.extensions_str()[0].to_string(),
+    ] {
+        hasher.update(field.as_bytes());
+        hasher.update([0]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,516 @@
+use markdown::{Constructs, Options, ParseOptions, mdast::Node};
+use regex::{Captures, Regex};
+use search_engine::{
+    PostsSearchEngine,
+    types::{RenderedPost, TocEntry},
+    utils::Post,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::helpers::{
+    highlight::highlight_code_block,
+    images::rewrite_images,
+    math::render_math,
+    sanitize::{SanitizeProfile, sanitize_html},
+    transclusion::expand_embeds,
+};
+
+static HEADING_TAG: OnceLock<Regex> = OnceLock::new();
+static CALLOUT_START: OnceLock<Regex> = OnceLock::new();
+static CODE_BLOCK: OnceLock<Regex> = OnceLock::new();
+static MATH: OnceLock<Regex> = OnceLock::new();
+static MARKDOWN_CONFIG: OnceLock<MarkdownConfig> = OnceLock::new();
+
+// Bump this whenever the rendering pipeline (markdown, highlighting, math,
+// sanitizing) or the post page template changes, so browsers don't keep the
+// pages rendered by the old one
+const RENDER_VERSION: &str = "1";
+
+/// Which optional markdown extensions the renderer enables. All of them are
+/// on by default and can be turned off with `MARKDOWN_GFM=false`,
+/// `MARKDOWN_CALLOUTS=false` and `MARKDOWN_MATH=false`.
+#[derive(Clone, Copy, Debug)]
+pub struct MarkdownConfig {
+    /// Tables, strikethrough, task lists, autolinks and footnotes.
+    pub gfm: bool,
+    /// Obsidian-style `> [!note]` callouts.
+    pub callouts: bool,
+    /// `$inline$` and `$$display$$` LaTeX rendered to MathML.
+    pub math: bool,
+}
+
+impl MarkdownConfig {
+    pub fn from_env() -> MarkdownConfig {
+        let enabled = |var: &str| {
+            std::env::var(var)
+                .map(|value| !matches!(value.trim().to_lowercase().as_str(), "false" | "0" | "off"))
+                .unwrap_or(true)
+        };
+
+        MarkdownConfig {
+            gfm: enabled("MARKDOWN_GFM"),
+            callouts: enabled("MARKDOWN_CALLOUTS"),
+            math: enabled("MARKDOWN_MATH"),
+        }
+    }
+}
+
+fn markdown_config() -> MarkdownConfig {
+    *MARKDOWN_CONFIG.get_or_init(MarkdownConfig::from_env)
+}
+
+fn options_for(config: MarkdownConfig) -> Options {
+    let constructs = if config.gfm {
+        Constructs::gfm()
+    } else {
+        Constructs::default()
+    };
+
+    Options {
+        parse: ParseOptions {
+            constructs: Constructs {
+                frontmatter: true,
+                math_text: config.math,
+                math_flow: config.math,
+                ..constructs
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub fn parse_options() -> ParseOptions {
+    options_for(markdown_config()).parse
+}
+
+/// Everything a rendered post depends on besides the posts themselves: the
+/// build, the renderer and the enabled extensions.
+pub fn render_fingerprint() -> String {
+    format!(
+        "{}-{}-{:?}",
+        env!("CARGO_PKG_VERSION"),
+        RENDER_VERSION,
+        markdown_config()
+    )
+}
+
+/// Renders an indexed post, going through the engine's cache of rendered posts.
+pub async fn render_indexed_post(engine: &PostsSearchEngine, post: &Post) -> Arc<RenderedPost> {
+    let embedded = engine.get_embedded_posts(post).await;
+    render_indexed_post_with(engine, post, &embedded)
+}
+
+/// Same as `render_indexed_post`, for callers that already fetched the notes
+/// the post embeds.
+pub fn render_indexed_post_with(
+    engine: &PostsSearchEngine,
+    post: &Post,
+    embedded: &HashMap<String, Post>,
+) -> Arc<RenderedPost> {
+    engine.rendered_post(post, embedded, |post| {
+        render_post(&post.content, &post.file_path, embedded)
+    })
+}
+
+/// `source_path` is the garden path of the post being rendered (`welcome.md`),
+/// used to resolve its relative images and to stop it from embedding itself.
+/// `embedded` are the notes it can transclude, by reference.
+pub fn render_post(md: &str, source_path: &str, embedded: &HashMap<String, Post>) -> RenderedPost {
+    let mut rendered =
+        render_post_with(md, markdown_config(), &[source_path.to_string()], embedded);
+    rendered.html = sanitize_html(&rendered.html, SanitizeProfile::Trusted);
+    rendered
+}
+
+/// Renders markdown that is not a garden post with plain CommonMark and the
+/// given sanitizing profile, none of the post extensions are applied.
+pub fn render_markdown(md: &str, profile: SanitizeProfile) -> String {
+    sanitize_html(&markdown::to_html(md), profile)
+}
+
+/// Renders a note transcluded into another one, `embed_stack` being the
+/// posts it is nested in.
+pub fn render_embedded_post(
+    md: &str,
+    embed_stack: &[String],
+    embedded: &HashMap<String, Post>,
+) -> String {
+    render_post_with(md, markdown_config(), embed_stack, embedded).html
+}
+
+fn render_post_with(
+    md: &str,
+    config: MarkdownConfig,
+    embed_stack: &[String],
+    embedded: &HashMap<String, Post>,
+) -> RenderedPost {
+    let options = options_for(config);
+    let mut html = markdown::to_html_with_options(md, &options).unwrap();
+    if config.callouts {
+        html = add_callouts(&html);
+    }
+
+    let tree = markdown::to_mdast(md, &options.parse).ok();
+    let toc = tree.as_ref().map(build_toc).unwrap_or_default();
+    if let Some(tree) = &tree {
+        // Display math is also a <pre><code>, so it has to go before the code blocks
+        if config.math {
+            html = render_math_nodes(&html, md, tree);
+        }
+        html = highlight_code_blocks(&html, tree);
+    }
+
+    // Image paths are relative to the note they are written in
+    if let Some(source_path) = embed_stack.last() {
+        html = rewrite_images(&html, source_path);
+    }
+    // Embedded notes come with their own heading anchors, so they go in last
+    let html = expand_embeds(&add_heading_anchors(&html, &toc), embed_stack, embedded);
+
+    RenderedPost {
+        html,
+        toc,
+        excerpt: tree.as_ref().and_then(first_paragraph),
+    }
+}
+
+/// Plain text of the first paragraph of a post, skipping the frontmatter and
+/// anything before it that isn't prose (headings, images, lists...).
+fn first_paragraph(tree: &Node) -> Option<String> {
+    tree.children()?
+        .iter()
+        .filter(|node| matches!(node, Node::Paragraph(_)))
+        .map(|node| node.to_string().trim().to_string())
+        .find(|text| !text.is_empty())
+}
+
+fn build_toc(tree: &Node) -> Vec<TocEntry> {
+    let mut headings = Vec::new();
+    collect_headings(tree, &mut headings);
+
+    let mut used_slugs: HashMap<String, usize> = HashMap::new();
+    headings
+        .into_iter()
+        .map(|(depth, text)| {
+            let base = slugify(&text);
+            let count = used_slugs.entry(base.clone()).or_insert(0);
+            let id = match *count {
+                0 => base,
+                n => format!("{}-{}", base, n),
+            };
+            *count += 1;
+
+            TocEntry { depth, text, id }
+        })
+        .collect()
+}
+
+fn collect_headings(node: &Node, headings: &mut Vec<(u8, String)>) {
+    if let Node::Heading(heading) = node {
+        headings.push((heading.depth, node.to_string().trim().to_string()));
+        return;
+    }
+
+    for child in node.children().into_iter().flatten() {
+        collect_headings(child, headings);
+    }
+}
+
+pub fn slugify(text: &str) -> String {
+    let slug = text
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() => Some(c),
+            ' ' | '-' | '_' => Some('-'),
+            _ => None,
+        })
+        .collect::<String>()
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if slug.is_empty() {
+        String::from("section")
+    } else {
+        slug
+    }
+}
+
+// The HTML headings come out in the same order as the mdast ones, so the n-th
+// heading tag gets the id of the n-th TOC entry.
+fn add_heading_anchors(html: &str, toc: &[TocEntry]) -> String {
+    let heading_tag = HEADING_TAG.get_or_init(|| Regex::new(r"<h([1-6])>").unwrap());
+    let mut entries = toc.iter();
+
+    heading_tag
+        .replace_all(html, |caps: &Captures| match entries.next() {
+            Some(entry) => format!(
+                r##"<h{level} id="{id}"><a class="heading-anchor" href="#{id}" aria-hidden="true">#</a>"##,
+                level = &caps[1],
+                id = entry.id
+            ),
+            None => caps[0].to_string(),
+        })
+        .into_owned()
+}
+
+// Same trick as the heading anchors: the n-th <pre><code> in the HTML is the
+// n-th code node of the mdast, which still has the raw code and the fence meta.
+fn highlight_code_blocks(html: &str, tree: &Node) -> String {
+    let code_block = CODE_BLOCK.get_or_init(|| {
+        Regex::new(r#"(?s)<pre><code(?: class="[^"]*")?>.*?</code></pre>\n?"#).unwrap()
+    });
+
+    let mut code_nodes = Vec::new();
+    collect_code_blocks(tree, &mut code_nodes);
+    let mut code_nodes = code_nodes.into_iter();
+
+    code_block
+        .replace_all(html, |caps: &Captures| match code_nodes.next() {
+            Some(code) if code.lang.as_deref() == Some("math") => display_math(&code.value),
+            Some(code) => {
+                highlight_code_block(&code.value, code.lang.as_deref(), code.meta.as_deref())
+            }
+            None => caps[0].to_string(),
+        })
+        .into_owned()
+}
+
+fn collect_code_blocks<'a>(node: &'a Node, code_blocks: &mut Vec<&'a markdown::mdast::Code>) {
+    if let Node::Code(code) = node {
+        code_blocks.push(code);
+        return;
+    }
+
+    for child in node.children().into_iter().flatten() {
+        collect_code_blocks(child, code_blocks);
+    }
+}
+
+// The markdown crate leaves math as <code class="language-math ..."> for the
+// client to render, these are swapped with MathML from the matching mdast nodes.
+fn render_math_nodes(html: &str, md: &str, tree: &Node) -> String {
+    let math = MATH.get_or_init(|| {
+        Regex::new(
+            r#"(?s)<pre><code class="language-math math-display"[^>]*>.*?</code></pre>\n?|<code class="language-math math-inline">.*?</code>"#,
+        )
+        .unwrap()
+    });
+
+    let mut math_nodes = Vec::new();
+    collect_math(tree, md, &mut math_nodes);
+    let mut math_nodes = math_nodes.into_iter();
+
+    math.replace_all(html, |caps: &Captures| match math_nodes.next() {
+        Some((tex, true)) => display_math(tex),
+        Some((tex, false)) => render_math(tex, false),
+        None => caps[0].to_string(),
+    })
+    .into_owned()
+}
+
+fn display_math(tex: &str) -> String {
+    format!(
+        r#"<div class="math-display">{}</div>"#,
+        render_math(tex, true)
+    )
+}
+
+// Collects the LaTeX of every math node and whether it is display math. Text
+// math written with double dollars is shown as display math too.
+fn collect_math<'a>(node: &'a Node, md: &str, math: &mut Vec<(&'a str, bool)>) {
+    match node {
+        Node::Math(block) => math.push((&block.value, true)),
+        Node::InlineMath(inline) => {
+            let display = inline
+                .position
+                .as_ref()
+                .and_then(|position| md.get(position.start.offset..))
+                .is_some_and(|source| source.starts_with("$$"));
+            math.push((&inline.value, display));
+        }
+        _ => {
+            for child in node.children().into_iter().flatten() {
+                collect_math(child, md, math);
+            }
+        }
+    }
+}
+
+// A callout is a blockquote whose first line is `[!type]`, optionally followed
+// by a title. The blockquote is kept and only tagged with the callout kind, so
+// nested content and the closing tag render as they normally would.
+fn add_callouts(html: &str) -> String {
+    let callout_start = CALLOUT_START.get_or_init(|| {
+        Regex::new(r"<blockquote>\n<p>\[!([A-Za-z-]+)\][+-]?[ \t]*([^\n]*?)(\n|</p>\n?)").unwrap()
+    });
+
+    callout_start
+        .replace_all(html, |caps: &Captures| {
+            let kind = callout_kind(&caps[1]);
+            let title = match caps[2].trim() {
+                "" => capitalize(&caps[1].to_lowercase()),
+                title => title.to_string(),
+            };
+            // When the body continues on the next line its paragraph is still open
+            let body_start = if &caps[3] == "\n" { "<p>" } else { "" };
+
+            format!(
+                r#"<blockquote class="callout callout-{kind}">
+<p class="callout-title">{title}</p>
+{body_start}"#
+            )
+        })
+        .into_owned()
+}
+
+// Obsidian has plenty of aliases for the same few styles
+fn callout_kind(name: &str) -> &'static str {
+    match name.to_lowercase().as_str() {
+        "abstract" | "summary" | "tldr" | "info" | "todo" => "info",
+        "tip" | "hint" | "important" => "tip",
+        "success" | "check" | "done" => "success",
+        "question" | "help" | "faq" => "question",
+        "warning" | "caution" | "attention" => "warning",
+        "failure" | "fail" | "missing" | "danger" | "error" | "bug" => "danger",
+        "example" => "example",
+        "quote" | "cite" => "quote",
+        _ => "note",
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headings_get_unique_slugs() {
+        let rendered = render_post(
+            "# Hello World!\n\n## Setup\n\ntext\n\n## Setup\n\n### Año 2025",
+            "test.md",
+            &HashMap::new(),
+        );
+
+        let ids: Vec<&str> = rendered.toc.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["hello-world", "setup", "setup-1", "año-2025"]);
+        assert_eq!(rendered.toc[3].depth, 3);
+        assert!(
+            rendered
+                .html
+                .contains(r##"<h2 id="setup-1"><a class="heading-anchor" href="#setup-1""##)
+        );
+    }
+
+    #[test]
+    fn test_callouts_are_rendered_as_styled_blockquotes() {
+        let rendered = render_post(
+            "> [!warning] Mind the gap\n> Some *body*\n\n> [!faq]\n\n> plain quote",
+            "test.md",
+            &HashMap::new(),
+        );
+
+        assert!(rendered.html.contains(
+            "<blockquote class=\"callout callout-warning\">\n<p class=\"callout-title\">Mind the gap</p>\n<p>Some <em>body</em></p>"
+        ));
+        assert!(rendered.html.contains(
+            "<blockquote class=\"callout callout-question\">\n<p class=\"callout-title\">Faq</p>\n</blockquote>"
+        ));
+        assert!(rendered.html.contains("<blockquote>\n<p>plain quote</p>"));
+    }
+
+    #[test]
+    fn test_code_fences_are_highlighted_with_fence_options() {
+        let rendered = render_post(
+            "```rust {2} showLineNumbers\nfn main() {\n    let x = \"<a>\";\n}\n```\n\n```\nplain & simple\n```",
+            "test.md",
+            &HashMap::new(),
+        );
+
+        assert!(rendered.html.contains(
+            r#"<pre class="hl-code line-numbers"><code class="language-rust"><span class="line"><span class="hl-source hl-rust">"#
+        ));
+        assert!(rendered.html.contains(r#"<span class="line highlighted">"#));
+        assert!(rendered.html.contains("&lt;a&gt;"));
+        assert!(rendered.html.contains(
+            r#"<pre class="hl-code"><code><span class="line">plain &amp; simple</span>"#
+        ));
+    }
+
+    #[test]
+    fn test_math_is_rendered_to_mathml() {
+        let rendered = render_post(
+            "Inline $x^2$, display $$\\frac{1}{2}$$ and $\\oops$\n\n$$\ny_1\n$$",
+            "test.md",
+            &HashMap::new(),
+        );
+
+        assert!(rendered.html.contains(r#"<math xmlns="http://www.w3.org/1998/Math/MathML" display="inline"><semantics><mrow><msup><mi>x</mi><mn>2</mn></msup>"#));
+        assert_eq!(
+            rendered
+                .html
+                .matches(r#"<div class="math-display"><math"#)
+                .count(),
+            2
+        );
+        assert!(
+            rendered
+                .html
+                .contains(r#"<span class="math-error" title="Unknown command \oops">\oops</span>"#)
+        );
+        assert!(!rendered.html.contains("language-math"));
+    }
+
+    #[test]
+    fn test_headings_inside_frontmatter_are_ignored() {
+        let rendered = render_post(
+            "---\ntitle: \"# Not a heading\"\n---\n\n# Title",
+            "test.md",
+            &HashMap::new(),
+        );
+
+        assert_eq!(rendered.toc.len(), 1);
+        assert_eq!(rendered.toc[0].text, "Title");
+    }
+
+    #[test]
+    fn test_posts_keep_generated_markup_but_not_scripts() {
+        let html = render_post(
+            "## Title\n\n<script>alert(1)</script>\n\n$x^2$ [link](javascript:alert(1))\n\n```rust\nfn main() {}\n```",
+            "test.md",
+            &HashMap::new(),
+        )
+        .html;
+
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("javascript:"));
+        assert!(html.contains(r#"<h2 id="title">"#));
+        assert!(html.contains("<math"));
+        assert!(html.contains("<msup>"));
+        assert!(html.contains(r#"class="hl-code"#));
+    }
+
+    #[test]
+    fn test_untrusted_markdown_only_keeps_basic_formatting() {
+        let html = render_markdown(
+            "**hi** <img src=x onerror=alert(1)> <span class=\"x\">y</span> [site](https://example.com)",
+            SanitizeProfile::Untrusted,
+        );
+
+        assert!(html.contains("<strong>hi</strong>"));
+        assert!(!html.contains("<img"));
+        assert!(!html.contains("<span"));
+        assert!(html.contains(r#"rel="noopener noreferrer nofollow ugc""#));
+    }
+}
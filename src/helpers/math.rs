@@ -0,0 +1,787 @@
+use std::fmt;
+
+// Groups, arguments and scripts nest this deep at most. Each level recurses
+// through the parser, so without a limit a formula could overflow the stack
+const MAX_NESTING: usize = 64;
+
+/// Error for a LaTeX expression that can't be converted, shown inline in
+/// place of the formula.
+#[derive(Debug, PartialEq)]
+pub struct MathError(String);
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for MathError {}
+
+/// Renders a LaTeX expression to MathML, falling back to a visible error
+/// with the original source if the expression is malformed.
+pub fn render_math(tex: &str, display: bool) -> String {
+    match latex_to_mathml(tex, display) {
+        Ok(mathml) => mathml,
+        Err(e) => format!(
+            r#"<span class="math-error" title="{}">{}</span>"#,
+            escape_html(&e.to_string()).replace('"', "&quot;"),
+            escape_html(tex.trim())
+        ),
+    }
+}
+
+/// Converts the subset of LaTeX commonly used in notes (scripts, fractions,
+/// roots, greek letters, operators, fonts, delimiters and matrices) to MathML
+/// Core, keeping the source as an annotation.
+pub fn latex_to_mathml(tex: &str, display: bool) -> Result<String, MathError> {
+    let mut parser = Parser {
+        chars: tex.chars().collect(),
+        pos: 0,
+        display,
+        depth: 0,
+    };
+
+    let (row, stop) = parser.parse_row()?;
+    match stop {
+        Stop::Eof => {}
+        Stop::CloseBrace => return Err(MathError(String::from("Unexpected }"))),
+        other => return Err(MathError(format!("Unexpected {}", other))),
+    }
+
+    Ok(format!(
+        r#"<math xmlns="http://www.w3.org/1998/Math/MathML" display="{}"><semantics><mrow>{}</mrow><annotation encoding="application/x-tex">{}</annotation></semantics></math>"#,
+        if display { "block" } else { "inline" },
+        row,
+        escape_html(tex.trim())
+    ))
+}
+
+/// What ended a row of the expression.
+#[derive(Debug, PartialEq)]
+enum Stop {
+    Eof,
+    CloseBrace,
+    Ampersand,
+    RowBreak,
+    Right,
+    End,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let token = match self {
+            Stop::Eof => "end of expression",
+            Stop::CloseBrace => "}",
+            Stop::Ampersand => "&",
+            Stop::RowBreak => "\\\\",
+            Stop::Right => "\\right",
+            Stop::End => "\\end",
+        };
+        write!(f, "{}", token)
+    }
+}
+
+/// A parsed piece of the expression, remembering whether it takes its
+/// scripts above and below (`\sum`, `\lim`...) in display mode.
+struct Atom {
+    mathml: String,
+    limits: bool,
+}
+
+impl Atom {
+    fn new(mathml: String) -> Atom {
+        Atom {
+            mathml,
+            limits: false,
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    display: bool,
+    // How many atoms are being parsed inside each other
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_row(&mut self) -> Result<(String, Stop), MathError> {
+        let mut row = String::new();
+
+        loop {
+            self.skip_whitespace();
+            let Some(c) = self.peek() else {
+                return Ok((row, Stop::Eof));
+            };
+
+            let atom = match c {
+                '}' => {
+                    self.pos += 1;
+                    return Ok((row, Stop::CloseBrace));
+                }
+                '&' => {
+                    self.pos += 1;
+                    return Ok((row, Stop::Ampersand));
+                }
+                '^' | '_' => Atom::new(String::from("<mrow></mrow>")),
+                '\\' => match self.command_name().as_str() {
+                    "\\" => {
+                        self.pos += 2;
+                        return Ok((row, Stop::RowBreak));
+                    }
+                    "right" => return Ok((row, Stop::Right)),
+                    "end" => return Ok((row, Stop::End)),
+                    _ => self.parse_atom()?,
+                },
+                _ => self.parse_atom()?,
+            };
+
+            row.push_str(&self.parse_scripts(atom)?);
+        }
+    }
+
+    // Reads the name of the command at the cursor without consuming it
+    fn command_name(&self) -> String {
+        let name: String = self.chars[self.pos + 1..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect();
+
+        match (name.is_empty(), self.chars.get(self.pos + 1)) {
+            (true, Some(c)) => c.to_string(),
+            _ => name,
+        }
+    }
+
+    fn parse_scripts(&mut self, base: Atom) -> Result<String, MathError> {
+        let mut sub = None;
+        let mut sup = None;
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('_') => {
+                    self.pos += 1;
+                    if sub.is_some() {
+                        return Err(MathError(String::from("Double subscript")));
+                    }
+                    sub = Some(self.parse_argument("_")?);
+                }
+                Some('^') => {
+                    self.pos += 1;
+                    if sup.is_some() {
+                        return Err(MathError(String::from("Double superscript")));
+                    }
+                    sup = Some(self.parse_argument("^")?);
+                }
+                Some('\'') => {
+                    self.pos += 1;
+                    let prime = String::from("<mo>′</mo>");
+                    sup = Some(match sup {
+                        Some(existing) => format!("<mrow>{}{}</mrow>", existing, prime),
+                        None => prime,
+                    });
+                }
+                _ => break,
+            }
+        }
+
+        let limits = base.limits && self.display;
+        let base = base.mathml;
+        Ok(match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) if limits => format!("<munder>{}{}</munder>", base, sub),
+            (None, Some(sup)) if limits => format!("<mover>{}{}</mover>", base, sup),
+            (Some(sub), Some(sup)) if limits => {
+                format!("<munderover>{}{}{}</munderover>", base, sub, sup)
+            }
+            (Some(sub), None) => format!("<msub>{}{}</msub>", base, sub),
+            (None, Some(sup)) => format!("<msup>{}{}</msup>", base, sup),
+            (Some(sub), Some(sup)) => format!("<msubsup>{}{}{}</msubsup>", base, sub, sup),
+        })
+    }
+
+    // A command argument or script: a braced group or a single atom
+    fn parse_argument(&mut self, command: &str) -> Result<String, MathError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('}') | Some('&') | Some('^') | Some('_') => {
+                Err(MathError(format!("Missing argument for {}", command)))
+            }
+            _ => Ok(self.parse_atom()?.mathml),
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<String, MathError> {
+        let (row, stop) = self.parse_row()?;
+        match stop {
+            Stop::CloseBrace => Ok(format!("<mrow>{}</mrow>", row)),
+            Stop::Eof => Err(MathError(String::from("Missing closing }"))),
+            other => Err(MathError(format!("Unexpected {}", other))),
+        }
+    }
+
+    // The raw text of a braced argument, for \text and the font commands
+    fn parse_text_argument(&mut self, command: &str) -> Result<String, MathError> {
+        self.skip_whitespace();
+        if self.peek() != Some('{') {
+            return Err(MathError(format!("Missing argument for \\{}", command)));
+        }
+        self.pos += 1;
+
+        let mut depth = 0;
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => return Ok(text),
+                '}' => depth -= 1,
+                _ => {}
+            }
+            text.push(c);
+        }
+
+        Err(MathError(String::from("Missing closing }")))
+    }
+
+    fn parse_atom(&mut self) -> Result<Atom, MathError> {
+        if self.depth >= MAX_NESTING {
+            return Err(MathError(String::from("Expression nested too deeply")));
+        }
+
+        self.depth += 1;
+        let atom = self.parse_nested_atom();
+        self.depth -= 1;
+        atom
+    }
+
+    fn parse_nested_atom(&mut self) -> Result<Atom, MathError> {
+        let c = self.peek().unwrap_or_default();
+
+        if c.is_ascii_digit() || c == '.' {
+            let start = self.pos;
+            while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                self.pos += 1;
+            }
+            let number: String = self.chars[start..self.pos].iter().collect();
+            return Ok(Atom::new(format!("<mn>{}</mn>", number)));
+        }
+
+        self.pos += 1;
+        let mathml = match c {
+            '{' => self.parse_group()?,
+            '\\' => return self.parse_command(),
+            '-' => String::from("<mo>−</mo>"),
+            '*' => String::from("<mo>∗</mo>"),
+            '<' => String::from("<mo>&lt;</mo>"),
+            '>' => String::from("<mo>&gt;</mo>"),
+            '~' => String::from(r#"<mspace width="0.333em"></mspace>"#),
+            c if c.is_alphabetic() => format!("<mi>{}</mi>", c),
+            c => format!("<mo>{}</mo>", escape_html(&c.to_string())),
+        };
+
+        Ok(Atom::new(mathml))
+    }
+
+    fn parse_command(&mut self) -> Result<Atom, MathError> {
+        // The backslash was already consumed
+        self.pos -= 1;
+        let name = self.command_name();
+        self.pos += 1 + name.chars().count();
+
+        if let Some(letter) = greek_letter(&name) {
+            return Ok(Atom::new(format!("<mi>{}</mi>", letter)));
+        }
+        if let Some(symbol) = symbol(&name) {
+            return Ok(Atom::new(format!("<mo>{}</mo>", symbol)));
+        }
+        if let Some(operator) = big_operator(&name) {
+            return Ok(Atom {
+                mathml: format!(
+                    r#"<mo largeop="true" movablelimits="true">{}</mo>"#,
+                    operator
+                ),
+                limits: !matches!(name.as_str(), "int" | "iint" | "iiint" | "oint"),
+            });
+        }
+        if let Some(spacing) = spacing(&name) {
+            return Ok(Atom::new(format!(
+                r#"<mspace width="{}"></mspace>"#,
+                spacing
+            )));
+        }
+        if let Some(accent) = accent(&name) {
+            let base = self.parse_argument(&format!("\\{}", name))?;
+            return Ok(Atom::new(format!(
+                r#"<mover accent="true">{}<mo stretchy="{}">{}</mo></mover>"#,
+                base,
+                matches!(
+                    name.as_str(),
+                    "overline" | "widehat" | "widetilde" | "overrightarrow"
+                ),
+                accent
+            )));
+        }
+
+        let mathml = match name.as_str() {
+            "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "arcsin" | "arccos" | "arctan"
+            | "sinh" | "cosh" | "tanh" | "log" | "ln" | "lg" | "exp" | "deg" | "dim" | "ker"
+            | "arg" | "gcd" | "Pr" => format!("<mi>{}</mi><mo>&#x2061;</mo>", name),
+            "lim" | "max" | "min" | "sup" | "inf" | "det" | "limsup" | "liminf" => {
+                return Ok(Atom {
+                    mathml: format!(r#"<mo movablelimits="true">{}</mo>"#, name),
+                    limits: true,
+                });
+            }
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.parse_argument("\\frac")?;
+                let denominator = self.parse_argument("\\frac")?;
+                format!("<mfrac>{}{}</mfrac>", numerator, denominator)
+            }
+            "binom" => {
+                let n = self.parse_argument("\\binom")?;
+                let k = self.parse_argument("\\binom")?;
+                format!(
+                    r#"<mrow><mo>(</mo><mfrac linethickness="0">{}{}</mfrac><mo>)</mo></mrow>"#,
+                    n, k
+                )
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+                if self.peek() == Some('[') {
+                    self.pos += 1;
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c != ']') {
+                        self.pos += 1;
+                    }
+                    if self.peek().is_none() {
+                        return Err(MathError(String::from("Missing ] in \\sqrt")));
+                    }
+                    let index: String = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    let radicand = self.parse_argument("\\sqrt")?;
+                    let index = latex_to_mathml_row(&index, self.display, self.depth)?;
+                    format!("<mroot>{}<mrow>{}</mrow></mroot>", radicand, index)
+                } else {
+                    format!("<msqrt>{}</msqrt>", self.parse_argument("\\sqrt")?)
+                }
+            }
+            "text" | "textrm" | "mbox" | "operatorname" => {
+                let text = self.parse_text_argument(&name)?;
+                match name.as_str() {
+                    "operatorname" => format!("<mi>{}</mi><mo>&#x2061;</mo>", escape_html(&text)),
+                    _ => format!("<mtext>{}</mtext>", escape_html(&text)),
+                }
+            }
+            "mathrm" | "mathbf" | "mathit" | "mathbb" | "mathcal" | "mathfrak" | "mathsf"
+            | "mathtt" | "boldsymbol" => {
+                let text = self.parse_text_argument(&name)?;
+                let variant = match name.as_str() {
+                    "mathrm" => "normal",
+                    "mathbf" | "boldsymbol" => "bold",
+                    "mathit" => "italic",
+                    "mathbb" => "double-struck",
+                    "mathcal" => "script",
+                    "mathfrak" => "fraktur",
+                    "mathsf" => "sans-serif",
+                    _ => "monospace",
+                };
+                match (name.as_str(), double_struck(&text)) {
+                    ("mathbb", Some(letter)) => format!("<mi>{}</mi>", letter),
+                    _ => format!(
+                        r#"<mi mathvariant="{}">{}</mi>"#,
+                        variant,
+                        escape_html(text.trim())
+                    ),
+                }
+            }
+            "left" => self.parse_fenced()?,
+            "begin" => self.parse_environment()?,
+            "{" | "}" | "#" | "$" | "%" | "_" => format!("<mo>{}</mo>", name),
+            "|" => String::from("<mo>‖</mo>"),
+            "&" => String::from("<mo>&amp;</mo>"),
+            "" => return Err(MathError(String::from("Expression ends with \\"))),
+            _ => return Err(MathError(format!("Unknown command \\{}", name))),
+        };
+
+        Ok(Atom::new(mathml))
+    }
+
+    fn parse_delimiter(&mut self, command: &str) -> Result<String, MathError> {
+        self.skip_whitespace();
+        let Some(c) = self.peek() else {
+            return Err(MathError(format!("Missing delimiter after \\{}", command)));
+        };
+
+        if c != '\\' {
+            self.pos += 1;
+            return Ok(match c {
+                '.' => String::new(),
+                '<' => String::from("&lt;"),
+                '>' => String::from("&gt;"),
+                c => c.to_string(),
+            });
+        }
+
+        let name = self.command_name();
+        self.pos += 1 + name.chars().count();
+        match name.as_str() {
+            "{" | "lbrace" => Ok(String::from("{")),
+            "}" | "rbrace" => Ok(String::from("}")),
+            "|" | "Vert" => Ok(String::from("‖")),
+            "langle" => Ok(String::from("⟨")),
+            "rangle" => Ok(String::from("⟩")),
+            "lvert" | "rvert" | "vert" => Ok(String::from("|")),
+            "lfloor" => Ok(String::from("⌊")),
+            "rfloor" => Ok(String::from("⌋")),
+            "lceil" => Ok(String::from("⌈")),
+            "rceil" => Ok(String::from("⌉")),
+            _ => Err(MathError(format!("Invalid delimiter \\{}", name))),
+        }
+    }
+
+    fn parse_fenced(&mut self) -> Result<String, MathError> {
+        let open = self.parse_delimiter("left")?;
+        let (row, stop) = self.parse_row()?;
+        if stop != Stop::Right {
+            return Err(MathError(String::from("\\left without matching \\right")));
+        }
+        self.pos += "\\right".len();
+        let close = self.parse_delimiter("right")?;
+
+        Ok(format!(
+            r#"<mrow><mo fence="true">{}</mo>{}<mo fence="true">{}</mo></mrow>"#,
+            open, row, close
+        ))
+    }
+
+    fn parse_environment(&mut self) -> Result<String, MathError> {
+        let environment = self.parse_text_argument("begin")?;
+        let (open, close) = match environment.as_str() {
+            "matrix" | "aligned" | "align" | "align*" | "gathered" | "array" => ("", ""),
+            "pmatrix" => ("(", ")"),
+            "bmatrix" => ("[", "]"),
+            "Bmatrix" => ("{", "}"),
+            "vmatrix" => ("|", "|"),
+            "Vmatrix" => ("‖", "‖"),
+            "cases" => ("{", ""),
+            _ => {
+                return Err(MathError(format!("Unknown environment {}", environment)));
+            }
+        };
+        if environment == "array" {
+            // Column alignments are not supported, just skipped
+            self.parse_text_argument("begin{array}")?;
+        }
+
+        let mut rows = Vec::new();
+        let mut cells = Vec::new();
+        loop {
+            let (cell, stop) = self.parse_row()?;
+            cells.push(format!("<mtd>{}</mtd>", cell));
+            match stop {
+                Stop::Ampersand => {}
+                Stop::RowBreak => rows.push(format!(
+                    "<mtr>{}</mtr>",
+                    cells.drain(..).collect::<String>()
+                )),
+                Stop::End => break,
+                Stop::Eof => {
+                    return Err(MathError(format!("Missing \\end{{{}}}", environment)));
+                }
+                other => return Err(MathError(format!("Unexpected {}", other))),
+            }
+        }
+        // A trailing \\ before \end leaves an empty last row behind
+        if cells.len() > 1 || cells.first().is_some_and(|cell| cell != "<mtd></mtd>") {
+            rows.push(format!("<mtr>{}</mtr>", cells.concat()));
+        }
+
+        self.pos += "\\end".len();
+        let end = self.parse_text_argument("end")?;
+        if end != environment {
+            return Err(MathError(format!(
+                "\\begin{{{}}} ended by \\end{{{}}}",
+                environment, end
+            )));
+        }
+
+        let table = format!("<mtable>{}</mtable>", rows.concat());
+        Ok(match (open, close) {
+            ("", "") => table,
+            (open, close) => format!(
+                r#"<mrow><mo fence="true">{}</mo>{}<mo fence="true">{}</mo></mrow>"#,
+                open, table, close
+            ),
+        })
+    }
+}
+
+// Square root indices are parsed on their own as they sit between brackets
+fn latex_to_mathml_row(tex: &str, display: bool, depth: usize) -> Result<String, MathError> {
+    let mut parser = Parser {
+        chars: tex.chars().collect(),
+        pos: 0,
+        display,
+        depth,
+    };
+    match parser.parse_row()? {
+        (row, Stop::Eof) => Ok(row),
+        (_, other) => Err(MathError(format!("Unexpected {}", other))),
+    }
+}
+
+fn greek_letter(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "vartheta" => "ϑ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "varpi" => "ϖ",
+        "rho" => "ρ",
+        "varrho" => "ϱ",
+        "sigma" => "σ",
+        "varsigma" => "ς",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "ell" => "ℓ",
+        "hbar" => "ℏ",
+        "infty" => "∞",
+        "emptyset" | "varnothing" => "∅",
+        "aleph" => "ℵ",
+        _ => return None,
+    })
+}
+
+fn symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "pm" => "±",
+        "mp" => "∓",
+        "times" => "×",
+        "div" => "÷",
+        "cdot" => "⋅",
+        "ast" => "∗",
+        "star" => "⋆",
+        "circ" => "∘",
+        "bullet" => "∙",
+        "oplus" => "⊕",
+        "otimes" => "⊗",
+        "cup" => "∪",
+        "cap" => "∩",
+        "setminus" => "∖",
+        "wedge" | "land" => "∧",
+        "vee" | "lor" => "∨",
+        "neg" | "lnot" => "¬",
+        "leq" | "le" => "≤",
+        "geq" | "ge" => "≥",
+        "neq" | "ne" => "≠",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "simeq" => "≃",
+        "cong" => "≅",
+        "propto" => "∝",
+        "ll" => "≪",
+        "gg" => "≫",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" => "∋",
+        "subset" => "⊂",
+        "supset" => "⊃",
+        "subseteq" => "⊆",
+        "supseteq" => "⊇",
+        "forall" => "∀",
+        "exists" => "∃",
+        "nexists" => "∄",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" | "implies" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "mapsto" => "↦",
+        "uparrow" => "↑",
+        "downarrow" => "↓",
+        "mid" => "∣",
+        "parallel" => "∥",
+        "perp" => "⊥",
+        "angle" => "∠",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "vert" => "|",
+        "Vert" => "‖",
+        "prime" => "′",
+        "degree" => "°",
+        _ => return None,
+    })
+}
+
+fn big_operator(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "sum" => "∑",
+        "prod" => "∏",
+        "coprod" => "∐",
+        "int" => "∫",
+        "iint" => "∬",
+        "iiint" => "∭",
+        "oint" => "∮",
+        "bigcup" => "⋃",
+        "bigcap" => "⋂",
+        "bigoplus" => "⨁",
+        "bigotimes" => "⨂",
+        "bigvee" => "⋁",
+        "bigwedge" => "⋀",
+        _ => return None,
+    })
+}
+
+fn spacing(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "," => "0.167em",
+        ":" | ">" => "0.222em",
+        ";" => "0.278em",
+        " " => "0.333em",
+        "quad" => "1em",
+        "qquad" => "2em",
+        "!" => "-0.167em",
+        _ => return None,
+    })
+}
+
+fn accent(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "hat" | "widehat" => "^",
+        "bar" | "overline" => "¯",
+        "vec" | "overrightarrow" => "→",
+        "dot" => "˙",
+        "ddot" => "¨",
+        "tilde" | "widetilde" => "~",
+        _ => return None,
+    })
+}
+
+fn double_struck(text: &str) -> Option<&'static str> {
+    Some(match text.trim() {
+        "C" => "ℂ",
+        "H" => "ℍ",
+        "N" => "ℕ",
+        "P" => "ℙ",
+        "Q" => "ℚ",
+        "R" => "ℝ",
+        "Z" => "ℤ",
+        _ => return None,
+    })
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latex_is_converted_to_mathml() {
+        let mathml =
+            latex_to_mathml(r"\frac{a}{b} + x^{2}_i \leq \sqrt[3]{\alpha}", false).unwrap();
+
+        assert!(mathml.contains("<mfrac><mrow><mi>a</mi></mrow><mrow><mi>b</mi></mrow></mfrac>"));
+        assert!(mathml.contains("<msubsup><mi>x</mi><mi>i</mi><mrow><mn>2</mn></mrow></msubsup>"));
+        assert!(mathml.contains("<mo>≤</mo>"));
+        assert!(mathml.contains("<mroot><mrow><mi>α</mi></mrow><mrow><mn>3</mn></mrow></mroot>"));
+    }
+
+    #[test]
+    fn test_display_operators_take_limits() {
+        let mathml = latex_to_mathml(r"\sum_{i=1}^{n} i", true).unwrap();
+
+        assert!(mathml.contains(r#"display="block""#));
+        assert!(mathml.contains("<munderover>"));
+    }
+
+    #[test]
+    fn test_malformed_expressions_are_errors() {
+        assert_eq!(
+            latex_to_mathml(r"\frac{a", false),
+            Err(MathError(String::from("Missing closing }")))
+        );
+        assert_eq!(
+            latex_to_mathml(r"\foo x", false),
+            Err(MathError(String::from("Unknown command \\foo")))
+        );
+        assert!(render_math(r"x^", false).contains(r#"class="math-error""#));
+    }
+
+    #[test]
+    fn test_deeply_nested_expressions_are_errors() {
+        let too_deep = MathError(String::from("Expression nested too deeply"));
+        let groups = format!("{}x{}", "{".repeat(100_000), "}".repeat(100_000));
+        assert_eq!(latex_to_mathml(&groups, false), Err(too_deep));
+
+        let fractions = format!("{}1{}", r"\frac{1}{".repeat(10_000), "}".repeat(10_000));
+        let roots = format!("{}x{}", r"\sqrt{".repeat(10_000), "}".repeat(10_000));
+        for nested in [fractions, roots] {
+            assert_eq!(
+                latex_to_mathml(&nested, true),
+                Err(MathError(String::from("Expression nested too deeply")))
+            );
+        }
+
+        let nested = format!("{}x{}", "{".repeat(10), "}".repeat(10));
+        assert!(latex_to_mathml(&nested, false).is_ok());
+    }
+}
@@ -1,5 +1,16 @@
+use chrono::{DateTime, Utc};
+use search_engine::stats::ReadingSpeed;
 use std::{collections::HashMap, fs};
 
+pub mod garden;
+pub mod highlight;
+pub mod images;
+pub mod markdown;
+pub mod math;
+pub mod og_image;
+pub mod sanitize;
+pub mod transclusion;
+
 pub fn read_env_file() -> HashMap<String, String> {
     let mut env_vars: HashMap<String, String> = HashMap::new();
     let content = fs::read_to_string(format!("{}/.env", env!("CARGO_MANIFEST_DIR"))).ok();
@@ -40,11 +51,68 @@ fn load_vars_from_environment() -> HashMap<String, String> {
         "REPO_SOURCE",
     ];
 
+    let optional_vars = [
+        "SITE_URL",
+        "SURREALDB_ENDPOINT",
+        "SURREALDB_USER",
+        "SURREALDB_PASS",
+        "REBUILD_INDEX",
+        "GARDEN_CONFIG_PATH",
+        "COLLECTIONS_PATH",
+        "READING_SPEED_PROSE",
+        "READING_SPEED_CODE",
+    ];
+
     required_vars
         .iter()
         .map(|&var| {
             let value = std::env::var(var).unwrap_or_else(|_| panic!("{} not defined", var));
             (var.to_string(), value)
         })
+        .chain(optional_vars.iter().filter_map(|&var| {
+            std::env::var(var)
+                .ok()
+                .map(|value| (var.to_string(), value))
+        }))
         .collect()
 }
+
+pub fn format_date(date: &DateTime<Utc>) -> String {
+    date.format("%-d %b %Y").to_string()
+}
+
+pub fn format_relative_date(date: &DateTime<Utc>) -> String {
+    let days = (Utc::now() - *date).num_days();
+
+    let plural = |amount: i64, unit: &str| match amount {
+        1 => format!("1 {} ago", unit),
+        n => format!("{} {}s ago", n, unit),
+    };
+
+    match days {
+        ..=0 => String::from("today"),
+        1 => String::from("yesterday"),
+        2..=13 => plural(days, "day"),
+        14..=59 => plural(days / 7, "week"),
+        60..=364 => plural(days / 30, "month"),
+        _ => plural(days / 365, "year"),
+    }
+}
+
+/// How fast posts are read, from `READING_SPEED_PROSE` (words per minute) and
+/// `READING_SPEED_CODE` (lines of code per minute).
+pub fn reading_speed(env_vars: &HashMap<String, String>) -> ReadingSpeed {
+    let rate = |var: &str| {
+        env_vars
+            .get(var)
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .filter(|rate| *rate > 0)
+    };
+    let default = ReadingSpeed::default();
+
+    ReadingSpeed {
+        prose_words_per_minute: rate("READING_SPEED_PROSE")
+            .unwrap_or(default.prose_words_per_minute),
+        code_lines_per_minute: rate("READING_SPEED_CODE").unwrap_or(default.code_lines_per_minute),
+    }
+}
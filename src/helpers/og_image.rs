@@ -0,0 +1,176 @@
+use maud::html;
+use resvg::{tiny_skia, usvg};
+use search_engine::utils::MdMetadata;
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+use crate::helpers::format_date;
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+const TITLE_LINE_CHARS: usize = 28;
+const TITLE_MAX_LINES: usize = 3;
+// Bump this whenever the card layout changes so old cached images are not served
+const TEMPLATE_VERSION: &str = "1";
+
+static FONT_DB: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+
+fn font_db() -> Arc<usvg::fontdb::Database> {
+    FONT_DB
+        .get_or_init(|| {
+            let mut db = usvg::fontdb::Database::new();
+            db.load_system_fonts();
+            db.load_fonts_dir("./static/fonts");
+            Arc::new(db)
+        })
+        .clone()
+}
+
+fn cache_dir() -> PathBuf {
+    PathBuf::from(std::env::var("OG_CACHE_PATH").unwrap_or_else(|_| "./cache/og".to_string()))
+}
+
+/// Returns the PNG social card for a post, rendering it only if the cached
+/// image for the current frontmatter does not exist yet. `reading_time` is
+/// in minutes.
+pub fn get_or_render_og_image(
+    app_name: &str,
+    metadata: &MdMetadata,
+    reading_time: u32,
+) -> io::Result<Vec<u8>> {
+    let cached_path = cache_dir().join(format!(
+        "{}.png",
+        metadata_hash(app_name, metadata, reading_time)
+    ));
+    if let Ok(png) = fs::read(&cached_path) {
+        return Ok(png);
+    }
+
+    let png = render_og_image(app_name, metadata, reading_time)?;
+    if let Err(e) = fs::create_dir_all(cache_dir()).and_then(|_| fs::write(&cached_path, &png)) {
+        log::warn!("Could not cache OG image {}: {}", cached_path.display(), e);
+    }
+
+    Ok(png)
+}
+
+fn metadata_hash(app_name: &str, metadata: &MdMetadata, reading_time: u32) -> String {
+    let mut hasher = Sha256::new();
+    for field in [
+        TEMPLATE_VERSION,
+        app_name,
+        &metadata.title,
+        metadata.topic.as_deref().unwrap_or_default(),
+        &metadata.date.to_rfc3339(),
+        &reading_time.to_string(),
+    ] {
+        hasher.update(field.as_bytes());
+        hasher.update([0]);
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+fn render_og_image(
+    app_name: &str,
+    metadata: &MdMetadata,
+    reading_time: u32,
+) -> io::Result<Vec<u8>> {
+    let svg = og_image_svg(app_name, metadata, reading_time);
+
+    let options = usvg::Options {
+        fontdb: font_db(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(&svg, &options)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(WIDTH, HEIGHT)
+        .ok_or_else(|| io::Error::other("Could not allocate OG image"))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+    pixmap.encode_png().map_err(io::Error::other)
+}
+
+fn og_image_svg(app_name: &str, metadata: &MdMetadata, reading_time: u32) -> String {
+    let title_lines = wrap_title(&metadata.title);
+    let footer = format!(
+        "{} · {} min read",
+        format_date(&metadata.date),
+        reading_time
+    );
+
+    html! {
+        svg
+        xmlns="http://www.w3.org/2000/svg"
+        width=(WIDTH)
+        height=(HEIGHT)
+        viewBox=(format!("0 0 {} {}", WIDTH, HEIGHT))
+        font-family="DejaVu Sans, sans-serif"
+        {
+            rect width=(WIDTH) height=(HEIGHT) fill="#1e242c" {}
+            rect width="16" height=(HEIGHT) fill="#f58a07" {}
+
+            @if let Some(topic) = &metadata.topic {
+                text x="96" y="130" font-size="30" letter-spacing="4" fill="#f58a07" {
+                    (topic.to_uppercase())
+                }
+            }
+
+            @for (index, line) in title_lines.iter().enumerate() {
+                text
+                x="96"
+                y=(230 + index * 84)
+                font-size="68"
+                font-weight="bold"
+                fill="#dbdfe5"
+                {
+                    (line)
+                }
+            }
+
+            line x1="96" y1="500" x2=(WIDTH - 96) y2="500" stroke="#323c49" stroke-width="2" {}
+            text x="96" y="560" font-size="30" fill="#dbdfe5" {
+                (footer)
+            }
+            text x=(WIDTH - 96) y="560" font-size="30" font-weight="bold" text-anchor="end" fill="#f58a07" {
+                (app_name)
+            }
+        }
+    }
+    .into_string()
+}
+
+// SVG text does not wrap by itself, so the title is split by words into
+// lines that fit the card, truncating with an ellipsis if it is too long.
+fn wrap_title(title: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for word in title.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + word.chars().count() >= TITLE_LINE_CHARS
+        {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > TITLE_MAX_LINES {
+        lines.truncate(TITLE_MAX_LINES);
+        if let Some(last) = lines.last_mut() {
+            last.push('…');
+        }
+    }
+
+    lines
+}
@@ -0,0 +1,118 @@
+use ammonia::Builder;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+static TRUSTED: OnceLock<Builder<'static>> = OnceLock::new();
+static UNTRUSTED: OnceLock<Builder<'static>> = OnceLock::new();
+
+const URL_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+// Everything the math renderer can output
+const MATHML_TAGS: [&str; 21] = [
+    "math",
+    "semantics",
+    "annotation",
+    "mrow",
+    "mi",
+    "mn",
+    "mo",
+    "mtext",
+    "mspace",
+    "msub",
+    "msup",
+    "msubsup",
+    "munder",
+    "mover",
+    "munderover",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "mtable",
+    "mtr",
+    "mtd",
+];
+
+/// How much of the HTML in a piece of content is kept.
+#[derive(Clone, Copy, Debug)]
+pub enum SanitizeProfile {
+    /// Garden posts: everything the markdown pipeline generates (highlighted
+    /// code, callouts, math, footnotes, embeds...) is allowed through.
+    Trusted,
+    /// Anything else: basic formatting and links only, with links marked as
+    /// user generated.
+    Untrusted,
+}
+
+/// Cleans rendered HTML with the allowlist of the given profile, removing any
+/// tag, attribute or URL scheme not in it.
+pub fn sanitize_html(html: &str, profile: SanitizeProfile) -> String {
+    let builder = match profile {
+        SanitizeProfile::Trusted => TRUSTED.get_or_init(trusted_builder),
+        SanitizeProfile::Untrusted => UNTRUSTED.get_or_init(untrusted_builder),
+    };
+
+    builder.clean(html).to_string()
+}
+
+fn trusted_builder() -> Builder<'static> {
+    let mut builder = Builder::default();
+    builder
+        .add_tags(["section", "picture", "source", "input"])
+        .add_tags(MATHML_TAGS)
+        .add_generic_attributes(["class", "id", "aria-hidden", "aria-label"])
+        .add_tag_attributes(
+            "a",
+            [
+                "data-footnote-ref",
+                "data-footnote-backref",
+                "aria-describedby",
+            ],
+        )
+        .add_tag_attributes("img", ["title", "srcset", "sizes", "loading", "decoding"])
+        .add_tag_attributes("source", ["srcset", "sizes", "type"])
+        .add_tag_attributes("section", ["data-footnotes"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .set_tag_attribute_value("input", "type", "checkbox")
+        .add_tag_attributes("math", ["xmlns", "display"])
+        .add_tag_attributes("mi", ["mathvariant"])
+        .add_tag_attributes("mo", ["fence", "stretchy", "largeop", "movablelimits"])
+        .add_tag_attributes("mover", ["accent"])
+        .add_tag_attributes("mspace", ["width"])
+        .add_tag_attributes("mfrac", ["linethickness"])
+        .add_tag_attributes("annotation", ["encoding"])
+        .url_schemes(HashSet::from(URL_SCHEMES))
+        .link_rel(None);
+
+    builder
+}
+
+fn untrusted_builder() -> Builder<'static> {
+    let mut builder = Builder::empty();
+    builder
+        .add_tags([
+            "a",
+            "blockquote",
+            "br",
+            "code",
+            "del",
+            "em",
+            "h1",
+            "h2",
+            "h3",
+            "h4",
+            "h5",
+            "h6",
+            "li",
+            "ol",
+            "p",
+            "pre",
+            "s",
+            "strong",
+            "ul",
+        ])
+        .add_tag_attributes("a", ["href"])
+        .url_schemes(HashSet::from(URL_SCHEMES))
+        .link_rel(Some("noopener noreferrer nofollow ugc"));
+
+    builder
+}
@@ -0,0 +1,221 @@
+use markdown::mdast::Node;
+use regex::{Captures, Regex};
+use search_engine::utils::{NOTE_EMBED_PATTERN, Post};
+use std::{collections::HashMap, sync::OnceLock};
+
+use crate::helpers::markdown::{render_embedded_post, slugify};
+
+// Embeds of embeds are fine, but past this point it is most likely a mistake
+const MAX_EMBED_DEPTH: usize = 4;
+
+static EMBED_PARAGRAPH: OnceLock<Regex> = OnceLock::new();
+
+/// Replaces every paragraph made only of an `![[note]]` or `![[note#Heading]]`
+/// embed with the rendered note, or that section of it. `embed_stack` holds
+/// the posts currently being rendered, from the outermost one, so a note that
+/// ends up embedding itself is shown as an error instead of looping forever.
+/// Notes are only looked up in `embedded`, the public posts found in the index
+/// for the references of the post.
+pub fn expand_embeds(
+    html: &str,
+    embed_stack: &[String],
+    embedded: &HashMap<String, Post>,
+) -> String {
+    let embed_paragraph = EMBED_PARAGRAPH
+        .get_or_init(|| Regex::new(&format!(r"<p>{}</p>\n?", NOTE_EMBED_PATTERN)).unwrap());
+
+    embed_paragraph
+        .replace_all(html, |caps: &Captures| {
+            let reference = caps[1].trim();
+            let heading = caps.get(2).map(|heading| heading.as_str().trim());
+
+            match render_embed(reference, heading, embed_stack, embedded) {
+                Ok(embed) => embed,
+                Err(message) => format!(
+                    r#"<div class="transclusion transclusion-error"><p>{}</p></div>"#,
+                    message
+                ),
+            }
+        })
+        .into_owned()
+}
+
+fn render_embed(
+    reference: &str,
+    heading: Option<&str>,
+    embed_stack: &[String],
+    embedded: &HashMap<String, Post>,
+) -> Result<String, String> {
+    let post = embedded
+        .get(reference)
+        .ok_or_else(|| format!("Could not find the note <code>{}</code>", reference))?;
+    let file_path = &post.file_path;
+
+    if embed_stack.contains(file_path) {
+        return Err(format!(
+            "<code>{}</code> is already being shown here, so it is not embedded again",
+            reference
+        ));
+    }
+    if embed_stack.len() > MAX_EMBED_DEPTH {
+        return Err(format!(
+            "Too many nested embeds to show <code>{}</code>",
+            reference
+        ));
+    }
+
+    // The heading is captured from the rendered HTML, its text is what the
+    // section and its anchor are found by
+    let heading_text = heading.map(unescape_html);
+    let md = match heading.zip(heading_text.as_deref()) {
+        Some((heading, heading_text)) => {
+            extract_section(&post.content, heading_text).ok_or_else(|| {
+                format!(
+                    "<code>{}</code> has no section called <code>{}</code>",
+                    reference, heading
+                )
+            })?
+        }
+        None => post.content.clone(),
+    };
+
+    let mut inner_stack = embed_stack.to_vec();
+    inner_stack.push(file_path.clone());
+    let content = render_embedded_post(&md, &inner_stack, embedded);
+
+    let post_url = format!(
+        "/posts/{}",
+        file_path.strip_suffix(".md").unwrap_or(file_path)
+    );
+    let (source_url, source_label) = match heading.zip(heading_text.as_deref()) {
+        Some((heading, heading_text)) => (
+            format!("{}#{}", post_url, slugify(heading_text)),
+            format!("{} › {}", reference, heading),
+        ),
+        None => (post_url, reference.to_string()),
+    };
+
+    Ok(format!(
+        r#"<div class="transclusion"><div class="transclusion-content">{}</div><a class="transclusion-source" href="{}">↳ {}</a></div>
+"#,
+        content, source_url, source_label
+    ))
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The markdown of the section under `heading`, up to the next heading of the
+/// same or a higher level.
+fn extract_section(md: &str, heading: &str) -> Option<String> {
+    let tree = markdown::to_mdast(md, &crate::helpers::markdown::parse_options()).ok()?;
+    let target_slug = slugify(heading);
+
+    let headings: Vec<(u8, String, usize)> = tree
+        .children()?
+        .iter()
+        .filter_map(|node| match node {
+            Node::Heading(h) => {
+                Some((h.depth, node.to_string(), h.position.as_ref()?.start.offset))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let index = headings
+        .iter()
+        .position(|(_, text, _)| slugify(text) == target_slug)?;
+    let (depth, _, start) = headings[index];
+    let end = headings[index + 1..]
+        .iter()
+        .find(|(other_depth, _, _)| *other_depth <= depth)
+        .map(|(_, _, offset)| *offset)
+        .unwrap_or(md.len());
+
+    Some(md[start..end].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use search_engine::utils::MdMetadata;
+
+    fn note(file_path: &str, content: &str) -> Post {
+        Post {
+            file_name: file_path.rsplit('/').next().unwrap().to_string(),
+            file_path: file_path.to_string(),
+            collection: String::from("garden"),
+            metadata: MdMetadata {
+                title: file_path.to_string(),
+                topic: None,
+                description: String::new(),
+                tags: Vec::new(),
+                date: chrono::Utc::now(),
+                is_draft: false,
+                post_source_url: String::new(),
+                stats: Default::default(),
+                aliases: Vec::new(),
+                revisions: Vec::new(),
+                lang: Default::default(),
+                translations: Vec::new(),
+                pinned: false,
+                weight: None,
+            },
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_only_embeds_notes_found_in_the_index() {
+        let embedded = HashMap::from([(
+            String::from("notes/mesa"),
+            note("notes/mesa.md", "The *Mesa* update"),
+        )]);
+        let html = "<p>![[notes/mesa]]</p>\n<p>![[drafts/secret]]</p>\n";
+
+        let expanded = expand_embeds(html, &[String::from("peak.md")], &embedded);
+
+        assert!(expanded.contains("The <em>Mesa</em> update"));
+        assert!(expanded.contains(r#"href="/posts/notes/mesa""#));
+        assert!(expanded.contains("Could not find the note <code>drafts/secret</code>"));
+    }
+
+    #[test]
+    fn test_embeds_sections_with_escaped_headings() {
+        let embedded = HashMap::from([(
+            String::from("notes/faq"),
+            note(
+                "notes/faq.md",
+                "# FAQ\n\n## Q&A\n\nAsk away\n\n## Other\n\nNope",
+            ),
+        )]);
+        let html = "<p>![[notes/faq#Q&amp;A]]</p>\n";
+
+        let expanded = expand_embeds(html, &[String::from("peak.md")], &embedded);
+
+        assert!(expanded.contains("Ask away"));
+        assert!(!expanded.contains("Nope"));
+        assert!(expanded.contains(&format!(r#"href="/posts/notes/faq#{}""#, slugify("Q&A"))));
+        assert!(expanded.contains("notes/faq › Q&amp;A"));
+    }
+
+    #[test]
+    fn test_extract_section_stops_at_the_next_sibling_heading() {
+        let md = "# Post\n\nintro\n\n## Setup\n\nfirst\n\n### Details\n\nmore\n\n## Usage\n\nlast";
+
+        assert_eq!(
+            extract_section(md, "setup").as_deref(),
+            Some("## Setup\n\nfirst\n\n### Details\n\nmore\n\n")
+        );
+        assert_eq!(
+            extract_section(md, "Usage").as_deref(),
+            Some("## Usage\n\nlast")
+        );
+        assert_eq!(extract_section(md, "Missing"), None);
+    }
+}
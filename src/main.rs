@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use actix_web::{App, HttpServer, middleware::Logger, web};
 use log::info;
-use search_engine::PostsSearchEngine;
+use search_engine::{PostsSearchEngine, types::DatabaseConfig};
 
 use crate::controllers::AppState;
 
@@ -69,6 +69,14 @@ async fn main() -> std::io::Result<()> {
         .expect("REPO_SOURCE not defined")
         .to_string();
 
+    let reading_speed = helpers::reading_speed(&env_vars);
+    let garden_config = helpers::garden::load_garden_config(&env_vars);
+
+    let site_url = env_vars
+        .get("SITE_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| String::from("http://localhost:3000"));
+
     info!("Initializing Spotify state...");
     let spotify_state = Arc::new(tokio::sync::Mutex::new(
         music_module::SpotifyState::from_refresh_token(
@@ -79,8 +87,31 @@ async fn main() -> std::io::Result<()> {
         ),
     ));
 
-    info!("Creating in-memory full-text search engine...");
-    let search_engine = Arc::new(PostsSearchEngine::new(repo_source.as_str(), "./garden").await);
+    let db_config = DatabaseConfig {
+        endpoint: env_vars
+            .get("SURREALDB_ENDPOINT")
+            .cloned()
+            .unwrap_or_else(|| DatabaseConfig::default().endpoint),
+        credentials: env_vars
+            .get("SURREALDB_USER")
+            .zip(env_vars.get("SURREALDB_PASS"))
+            .map(|(user, pass)| (user.to_string(), pass.to_string())),
+        force_rebuild: env_vars.get("REBUILD_INDEX").is_some_and(|value| {
+            matches!(value.trim().to_lowercase().as_str(), "true" | "1" | "on")
+        }),
+    };
+
+    info!(
+        "Creating full-text search engine on {}...",
+        db_config.endpoint
+    );
+    let search_engine = Arc::new(
+        PostsSearchEngine::new(repo_source.as_str(), &garden_config.collections, &db_config)
+            .await
+            .map_err(|e| {
+                std::io::Error::other(format!("Failed to create the search engine: {}", e))
+            })?,
+    );
     info!("Search engine created correctly");
 
     info!("Server starting on port 3000");
@@ -91,6 +122,7 @@ async fn main() -> std::io::Result<()> {
             .service(actix_files::Files::new("/_static", "./static").show_files_listing())
             .app_data(web::Data::new(AppState {
                 app_name: String::from("Gus' digital garden"),
+                site_url: site_url.clone(),
                 lichess_state: chess_module::LichessState {
                     lichess_token: lichess_token.clone(),
                     lichess_username: lichess_username.clone(),
@@ -98,15 +130,23 @@ async fn main() -> std::io::Result<()> {
                 spotify_state: Arc::clone(&spotify_state),
                 steam_state: games_module::SteamState::new(steam_token.clone(), steam_id.clone()),
                 post_search_engine: Arc::clone(&search_engine),
+                reading_speed,
             }))
+            .configure(controllers::attachments_controller::configure_services)
             .configure(controllers::posts_controller::configure_services)
             .configure(controllers::news_controller::configure_services)
             .configure(controllers::metadata_controller::configure_services)
+            .configure(controllers::preview_controller::configure_services)
             .configure(controllers::steam_controller::configure_services)
             .configure(controllers::chess_controller::configure_services)
             .configure(controllers::graph_controller::configure_services)
             .configure(controllers::music_controller::configure_services)
             .configure(controllers::routines_controller::configure_services)
+            .configure(controllers::feed_controller::configure_services)
+            .configure(controllers::seo_controller::configure_services)
+            .configure(controllers::og_controller::configure_services)
+            .configure(controllers::diagnostics_controller::configure_services)
+            .configure(controllers::api_controller::configure_services)
             .service(controllers::fallback_controller::fallback_route) // This service should be last one in the list because it matches any string
     })
     .bind(("0.0.0.0", 3000))?
@@ -0,0 +1,80 @@
+// Shows a small preview of a post when hovering an internal link to it
+(function () {
+  const HOVER_DELAY_MS = 350;
+  const MARGIN = 8;
+
+  let popover = null;
+  let currentLink = null;
+  let hoverTimer = null;
+
+  function getPopover() {
+    if (!popover) {
+      popover = document.createElement("div");
+      popover.id = "link-preview";
+      popover.setAttribute("role", "tooltip");
+      document.body.appendChild(popover);
+    }
+    return popover;
+  }
+
+  function previewPath(link) {
+    if (link.origin !== window.location.origin) return null;
+    if (!link.pathname.startsWith("/posts/")) return null;
+    // Links to a heading of the current post don't need a preview
+    if (link.pathname === window.location.pathname) return null;
+    return link.pathname.slice("/posts/".length);
+  }
+
+  function place(link) {
+    const preview = getPopover();
+    const rect = link.getBoundingClientRect();
+    const left = Math.min(
+      Math.max(rect.left, MARGIN),
+      window.innerWidth - preview.offsetWidth - MARGIN,
+    );
+    let top = rect.bottom + MARGIN;
+    if (top + preview.offsetHeight > window.innerHeight - MARGIN) {
+      top = rect.top - preview.offsetHeight - MARGIN;
+    }
+    preview.style.left = `${left}px`;
+    preview.style.top = `${top}px`;
+  }
+
+  function show(link, path) {
+    const preview = getPopover();
+    htmx
+      .ajax("GET", `/preview/${path}`, { target: preview, swap: "innerHTML" })
+      .then(() => {
+        if (currentLink !== link) return;
+        preview.classList.add("visible");
+        place(link);
+      });
+  }
+
+  function hide() {
+    clearTimeout(hoverTimer);
+    currentLink = null;
+    if (popover) popover.classList.remove("visible");
+  }
+
+  document.addEventListener("mouseover", function (evt) {
+    const link = evt.target.closest(".prose a[href]");
+    if (!link || link === currentLink) return;
+
+    const path = previewPath(link);
+    if (!path) return;
+
+    hide();
+    currentLink = link;
+    hoverTimer = setTimeout(() => show(link, path), HOVER_DELAY_MS);
+  });
+
+  document.addEventListener("mouseout", function (evt) {
+    if (currentLink && !currentLink.contains(evt.relatedTarget)) hide();
+  });
+
+  document.addEventListener("click", hide);
+  document.addEventListener("htmx:beforeSwap", function (evt) {
+    if (evt.detail.target !== popover) hide();
+  });
+})();